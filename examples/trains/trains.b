@@ -0,0 +1,99 @@
+% Michalski's trains, in the layout of Aleph's examples: which trains are eastbound?
+% Each train has cars, described by their length, roof, shape, number of wheels and load.
+
+:- modeh(1, eastbound(+train)).
+:- modeb(*, has_car(+train, -car)).
+:- modeb(1, short(+car)).
+:- modeb(1, long(+car)).
+:- modeb(1, closed(+car)).
+:- modeb(1, open_car(+car)).
+:- modeb(1, shape(+car, #shape)).
+:- modeb(1, wheels(+car, #int)).
+:- modeb(1, load(+car, #shape, #int)).
+
+:- determination(eastbound/1, has_car/2).
+:- determination(eastbound/1, short/1).
+:- determination(eastbound/1, closed/1).
+
+% Rules are skipped by the importer
+car_count(T, N) :- findall(C, has_car(T, C), Cs), length(Cs, N).
+
+% east1
+has_car(east1, car_11). has_car(east1, car_12). has_car(east1, car_13). has_car(east1, car_14).
+long(car_11). short(car_12). long(car_13). short(car_14).
+open_car(car_11). closed(car_12). open_car(car_13). open_car(car_14).
+shape(car_11, rectangle). shape(car_12, rectangle). shape(car_13, rectangle). shape(car_14, rectangle).
+wheels(car_11, 3). wheels(car_12, 2). wheels(car_13, 2). wheels(car_14, 2).
+load(car_11, rectangle, 3). load(car_12, triangle, 1). load(car_13, hexagon, 1). load(car_14, circle, 1).
+
+% east2
+has_car(east2, car_21). has_car(east2, car_22). has_car(east2, car_23).
+short(car_21). short(car_22). short(car_23).
+closed(car_21). open_car(car_22). open_car(car_23).
+shape(car_21, bucket). shape(car_22, bucket). shape(car_23, rectangle).
+wheels(car_21, 2). wheels(car_22, 2). wheels(car_23, 2).
+load(car_21, circle, 1). load(car_22, rectangle, 1). load(car_23, triangle, 1).
+
+% east3
+has_car(east3, car_31). has_car(east3, car_32). has_car(east3, car_33).
+short(car_31). long(car_32). short(car_33).
+closed(car_31). closed(car_32). open_car(car_33).
+shape(car_31, rectangle). shape(car_32, hexagon). shape(car_33, rectangle).
+wheels(car_31, 2). wheels(car_32, 3). wheels(car_33, 2).
+load(car_31, triangle, 1). load(car_32, triangle, 1). load(car_33, circle, 2).
+
+% east4
+has_car(east4, car_41). has_car(east4, car_42). has_car(east4, car_43). has_car(east4, car_44).
+short(car_41). short(car_42). short(car_43). short(car_44).
+open_car(car_41). open_car(car_42). closed(car_43). open_car(car_44).
+shape(car_41, u_shaped). shape(car_42, bucket). shape(car_43, rectangle). shape(car_44, rectangle).
+wheels(car_41, 2). wheels(car_42, 2). wheels(car_43, 2). wheels(car_44, 2).
+load(car_41, triangle, 1). load(car_42, rectangle, 1). load(car_43, circle, 1). load(car_44, rectangle, 1).
+
+% east5
+has_car(east5, car_51). has_car(east5, car_52). has_car(east5, car_53).
+short(car_51). long(car_52). short(car_53).
+open_car(car_51). open_car(car_52). closed(car_53).
+shape(car_51, bucket). shape(car_52, rectangle). shape(car_53, rectangle).
+wheels(car_51, 2). wheels(car_52, 3). wheels(car_53, 2).
+load(car_51, triangle, 1). load(car_52, rectangle, 1). load(car_53, circle, 1).
+
+% west6
+has_car(west6, car_61). has_car(west6, car_62).
+long(car_61). short(car_62).
+closed(car_61). open_car(car_62).
+shape(car_61, rectangle). shape(car_62, rectangle).
+wheels(car_61, 2). wheels(car_62, 2).
+load(car_61, circle, 3). load(car_62, triangle, 1).
+
+% west7
+has_car(west7, car_71). has_car(west7, car_72). has_car(west7, car_73).
+short(car_71). short(car_72). long(car_73).
+open_car(car_71). open_car(car_72). open_car(car_73).
+shape(car_71, rectangle). shape(car_72, u_shaped). shape(car_73, rectangle).
+wheels(car_71, 2). wheels(car_72, 2). wheels(car_73, 2).
+load(car_71, circle, 1). load(car_72, triangle, 1). load(car_73, rectangle, 1).
+
+% west8
+has_car(west8, car_81). has_car(west8, car_82).
+long(car_81). short(car_82).
+closed(car_81). open_car(car_82).
+shape(car_81, rectangle). shape(car_82, u_shaped).
+wheels(car_81, 3). wheels(car_82, 2).
+load(car_81, rectangle, 1). load(car_82, circle, 1).
+
+% west9
+has_car(west9, car_91). has_car(west9, car_92). has_car(west9, car_93). has_car(west9, car_94).
+short(car_91). long(car_92). short(car_93). short(car_94).
+open_car(car_91). closed(car_92). open_car(car_93). open_car(car_94).
+shape(car_91, u_shaped). shape(car_92, rectangle). shape(car_93, rectangle). shape(car_94, u_shaped).
+wheels(car_91, 2). wheels(car_92, 2). wheels(car_93, 2). wheels(car_94, 2).
+load(car_91, circle, 1). load(car_92, rectangle, 1). load(car_93, rectangle, 1). load(car_94, circle, 1).
+
+% west10
+has_car(west10, car_101). has_car(west10, car_102).
+short(car_101). long(car_102).
+open_car(car_101). open_car(car_102).
+shape(car_101, u_shaped). shape(car_102, rectangle).
+wheels(car_101, 2). wheels(car_102, 2).
+load(car_101, rectangle, 1). load(car_102, rectangle, 2).
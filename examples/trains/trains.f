@@ -0,0 +1,5 @@
+eastbound(east1).
+eastbound(east2).
+eastbound(east3).
+eastbound(east4).
+eastbound(east5).
@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+use typedb_driver::Promise;
+
+use crate::{
+    CoverageOracle, Instance, TypeDBHelper,
+    clause::{Clause, ClauseVariable},
+    language::{HypothesisLanguage, SchemaType},
+};
+
+// Isolation forest over first-order trees. Splits use random refinements of the test prefix, as in TILDE,
+// so anomalies are instances that are isolated by few relational tests.
+// Clauses are tested against TypeDB, or any other CoverageOracle such as crate::memory::FactStore.
+pub struct IsolationForestTask<O: CoverageOracle = TypeDBHelper> {
+    pub oracle: O,
+
+    pub target_type: SchemaType, // The type whose instances we score.
+    pub language: HypothesisLanguage,
+    pub instances: Vec<Instance>,
+}
+
+pub struct IsolationForest {
+    pub trees: Vec<IsolationTree>,
+    pub sample_size: usize,
+}
+
+pub enum IsolationTree {
+    Leaf { size: usize },
+    Inner { test: Clause, left: Box<IsolationTree>, right: Box<IsolationTree> },
+}
+
+impl IsolationForestTask {
+    pub fn discover(
+        typedb: TypeDBHelper,
+        language: HypothesisLanguage,
+        target_type_label: &str,
+    ) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let query = format!("match ${} isa {};", ClauseVariable::INSTANCE_VAR_NAME, target_type);
+        let tx = typedb.read_tx()?;
+        let instances = tx
+            .query(query.as_str())
+            .resolve()?
+            .into_rows()
+            .map(|row| Ok(row?.get(ClauseVariable::INSTANCE_VAR_NAME).unwrap().unwrap().into()))
+            .collect::<Result<Vec<Instance>, typedb_driver::Error>>()?;
+        Ok(Self { oracle: typedb, target_type, language, instances })
+    }
+}
+
+impl<O: CoverageOracle> IsolationForestTask<O> {
+    // Refinements tried at each node before giving up on isolating the sample further
+    const MAX_SPLIT_ATTEMPTS: usize = 20;
+
+    // The instances of the target type, as the oracle finds them. They are ordered by IID, so that samples only
+    // depend on the seed.
+    pub fn new(oracle: O, language: HypothesisLanguage, target_type_label: &str) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let initial_clause = Clause::new_from_head(std::slice::from_ref(&target_type), &language.schema);
+        let instances = oracle.test_clause(&initial_clause)?.into_iter().sorted_by_key(Instance::sort_key).collect();
+        Ok(Self { oracle, target_type, language, instances })
+    }
+
+    pub fn deconstruct(self) -> O {
+        self.oracle
+    }
+
+    fn initial_clause(&self) -> Clause {
+        Clause::new_from_head(std::slice::from_ref(&self.target_type), &self.language.schema)
+    }
+
+    // Each tree is grown on a random sample, to at most log2(sample_size) deep.
+    // Scores are normalised by the average path length of a sample, so samples need at least 2 instances.
+    pub fn search(
+        &self,
+        num_trees: usize,
+        sample_size: usize,
+        seed: u64,
+    ) -> Result<IsolationForest, typedb_driver::Error> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let sample_size = sample_size.min(self.instances.len());
+        if sample_size < 2 || num_trees == 0 {
+            return Err(typedb_driver::Error::Other(format!(
+                "Expected at least one tree and a sample of at least 2 instances, got {num_trees} trees of {sample_size}"
+            )));
+        }
+        let max_depth = (sample_size as f64).log2().ceil() as usize;
+        let trees = (0..num_trees)
+            .map(|_| {
+                let sample: HashSet<Instance> =
+                    self.instances.choose_multiple(&mut rng, sample_size).cloned().collect();
+                self.grow(self.initial_clause(), sample, 0, max_depth, &mut rng)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(IsolationForest { trees, sample_size })
+    }
+
+    fn grow(
+        &self,
+        test_prefix: Clause,
+        sample: HashSet<Instance>,
+        depth: usize,
+        max_depth: usize,
+        rng: &mut StdRng,
+    ) -> Result<IsolationTree, typedb_driver::Error> {
+        if sample.len() <= 1 || depth >= max_depth {
+            return Ok(IsolationTree::Leaf { size: sample.len() });
+        }
+        let mut refinements = test_prefix.refine(&self.language);
+        refinements.shuffle(rng);
+        for refined in refinements.into_iter().take(Self::MAX_SPLIT_ATTEMPTS) {
+            let covered = self.oracle.test_clause(&refined)?;
+            let (left, right): (HashSet<Instance>, HashSet<Instance>) =
+                sample.iter().cloned().partition(|instance| covered.contains(instance));
+            if !left.is_empty() && !right.is_empty() {
+                // As in TILDE, the right branch keeps the test prefix.
+                let left = Box::new(self.grow(refined.clone(), left, depth + 1, max_depth, rng)?);
+                let right = Box::new(self.grow(test_prefix, right, depth + 1, max_depth, rng)?);
+                return Ok(IsolationTree::Inner { test: refined, left, right });
+            }
+        }
+        Ok(IsolationTree::Leaf { size: sample.len() })
+    }
+}
+
+impl IsolationForest {
+    // Anomaly score in (0, 1] for each instance. Scores close to 1 are anomalies; well below 0.5 are normal.
+    pub fn scores(
+        &self,
+        oracle: &impl CoverageOracle,
+        instances: &[Instance],
+    ) -> Result<HashMap<Instance, f64>, typedb_driver::Error> {
+        let mut total_path_lengths: HashMap<Instance, f64> = instances.iter().map(|i| (i.clone(), 0f64)).collect();
+        for tree in &self.trees {
+            tree.add_path_lengths(oracle, instances.iter().cloned().collect(), 0, &mut total_path_lengths)?;
+        }
+        let normalisation = average_path_length(self.sample_size);
+        Ok(total_path_lengths
+            .into_iter()
+            .map(|(instance, total)| {
+                let mean_path_length = total / self.trees.len() as f64;
+                (instance, 2f64.powf(-mean_path_length / normalisation))
+            })
+            .collect())
+    }
+}
+
+impl IsolationTree {
+    fn add_path_lengths(
+        &self,
+        oracle: &impl CoverageOracle,
+        instances: HashSet<Instance>,
+        depth: usize,
+        total_path_lengths: &mut HashMap<Instance, f64>,
+    ) -> Result<(), typedb_driver::Error> {
+        match self {
+            IsolationTree::Leaf { size } => {
+                // Unbuilt subtree of the leaf's size
+                let path_length = depth as f64 + average_path_length(*size);
+                instances.iter().for_each(|instance| *total_path_lengths.get_mut(instance).unwrap() += path_length);
+            }
+            IsolationTree::Inner { test, left, right } => {
+                let covered = oracle.test_clause(test)?;
+                let (left_instances, right_instances) =
+                    instances.into_iter().partition(|instance| covered.contains(instance));
+                left.add_path_lengths(oracle, left_instances, depth + 1, total_path_lengths)?;
+                right.add_path_lengths(oracle, right_instances, depth + 1, total_path_lengths)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Average path length of an unsuccessful search in a binary search tree of n nodes
+fn average_path_length(n: usize) -> f64 {
+    const EULER_GAMMA: f64 = 0.5772156649;
+    match n {
+        0 | 1 => 0f64,
+        2 => 1f64,
+        _ => 2f64 * (((n - 1) as f64).ln() + EULER_GAMMA) - 2f64 * (n - 1) as f64 / n as f64,
+    }
+}
@@ -1,9 +1,11 @@
 use std::{
-    collections::{BTreeSet, HashMap},
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
     fmt::Formatter,
 };
 
 use itertools::Itertools;
+use typedb_driver::concept::ValueType;
 
 use crate::{
     INDENT,
@@ -16,6 +18,15 @@ pub struct ClauseVariable(pub String);
 
 impl ClauseVariable {
     pub const INSTANCE_VAR_NAME: &'static str = "instance";
+
+    // $instance, $instance_1, $instance_2, ... for each position in the target tuple
+    pub fn head(position: usize) -> Self {
+        match position {
+            0 => Self(Self::INSTANCE_VAR_NAME.to_owned()),
+            _ => Self(format!("{}_{}", Self::INSTANCE_VAR_NAME, position)),
+        }
+    }
+
     pub(crate) fn name(&self) -> &str {
         self.0.as_str()
     }
@@ -27,24 +38,25 @@ impl std::fmt::Display for ClauseVariable {
     }
 }
 
-#[derive(Debug, Clone)]
-enum ValueComparator {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueComparator {
     Eq,
     Neq,
+    Lt,
     Lte,
     Gte,
+    Gt,
 }
 
 impl ValueComparator {
-    const VALUES: [ValueComparator; 4] =
-        [ValueComparator::Eq, ValueComparator::Neq, ValueComparator::Lte, ValueComparator::Gte];
-
     fn to_typeql(&self) -> typeql::token::Comparator {
         match self {
             ValueComparator::Eq => typeql::token::Comparator::Eq,
             ValueComparator::Neq => typeql::token::Comparator::Neq,
+            ValueComparator::Lt => typeql::token::Comparator::Lt,
             ValueComparator::Lte => typeql::token::Comparator::Lte,
             ValueComparator::Gte => typeql::token::Comparator::Gte,
+            ValueComparator::Gt => typeql::token::Comparator::Gt,
         }
     }
 }
@@ -54,7 +66,7 @@ impl std::fmt::Display for ValueComparator {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ClauseLiteral {
     Has { owner: ClauseVariable, type_: SchemaType, attribute: ClauseVariable },
     HasValue { owner: ClauseVariable, type_: SchemaType, value: typedb_driver::concept::value::Value },
@@ -62,6 +74,11 @@ pub enum ClauseLiteral {
     Isa { instance: ClauseVariable, type_: SchemaType },
     CompareVariables { lhs: ClauseVariable, comparator: ValueComparator, rhs: ClauseVariable },
     CompareConstant { lhs: ClauseVariable, comparator: ValueComparator, rhs: typedb_driver::concept::value::Value },
+    // Variables introduced inside the negation are local to it.
+    Not { conjunction: Vec<ClauseLiteral> },
+    Or { branches: Vec<Vec<ClauseLiteral>> },
+    // Binds the outputs to each row returned by a TypeQL function, e.g. the theory being learnt.
+    Call { function: String, outputs: Vec<ClauseVariable> },
 }
 
 impl ClauseLiteral {
@@ -86,6 +103,83 @@ impl ClauseLiteral {
             ClauseLiteral::CompareConstant { lhs, comparator, rhs } => {
                 format!("{lhs} {comparator} {rhs}")
             }
+            ClauseLiteral::Not { conjunction } => {
+                format!("not {{ {}; }}", conjunction.iter().map(|literal| literal.to_typeql()).join("; "))
+            }
+            ClauseLiteral::Or { branches } => branches
+                .iter()
+                .map(|branch| format!("{{ {}; }}", branch.iter().map(|literal| literal.to_typeql()).join("; ")))
+                .join(" or "),
+            ClauseLiteral::Call { function, outputs } => {
+                format!("let {} in {function}()", outputs.iter().join(", "))
+            }
+        }
+    }
+
+    // Number of literals, including those nested in negations.
+    fn size(&self) -> usize {
+        match self {
+            ClauseLiteral::Not { conjunction } => 1 + conjunction.iter().map(|literal| literal.size()).sum::<usize>(),
+            ClauseLiteral::Or { branches } => {
+                1 + branches.iter().flatten().map(|literal| literal.size()).sum::<usize>()
+            }
+            _ => 1,
+        }
+    }
+
+    // The variables the literal binds in the conjunction it is part of. Those of negations and disjunctions are local.
+    fn bound_variables(&self) -> Vec<&ClauseVariable> {
+        match self {
+            ClauseLiteral::Has { owner, attribute, .. } => vec![owner, attribute],
+            ClauseLiteral::HasValue { owner, .. } => vec![owner],
+            ClauseLiteral::Links { relation, player, .. } => vec![relation, player],
+            ClauseLiteral::Isa { instance, .. } => vec![instance],
+            ClauseLiteral::Call { outputs, .. } => outputs.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Every variable the literal mentions, including those local to its negations and disjunctions
+    fn variables(&self) -> Vec<&ClauseVariable> {
+        match self {
+            ClauseLiteral::CompareVariables { lhs, rhs, .. } => vec![lhs, rhs],
+            ClauseLiteral::CompareConstant { lhs, .. } => vec![lhs],
+            ClauseLiteral::Not { conjunction } => conjunction.iter().flat_map(|literal| literal.variables()).collect(),
+            ClauseLiteral::Or { branches } => {
+                branches.iter().flatten().flat_map(|literal| literal.variables()).collect()
+            }
+            _ => self.bound_variables(),
+        }
+    }
+
+    fn map_variables(&self, map: &impl Fn(&ClauseVariable) -> ClauseVariable) -> ClauseLiteral {
+        let map_all = |literals: &[ClauseLiteral]| literals.iter().map(|literal| literal.map_variables(map)).collect();
+        match self {
+            ClauseLiteral::Has { owner, type_, attribute } => {
+                ClauseLiteral::Has { owner: map(owner), type_: type_.clone(), attribute: map(attribute) }
+            }
+            ClauseLiteral::HasValue { owner, type_, value } => {
+                ClauseLiteral::HasValue { owner: map(owner), type_: type_.clone(), value: value.clone() }
+            }
+            ClauseLiteral::Links { relation, role, player } => {
+                ClauseLiteral::Links { relation: map(relation), role: role.clone(), player: map(player) }
+            }
+            ClauseLiteral::Isa { instance, type_ } => {
+                ClauseLiteral::Isa { instance: map(instance), type_: type_.clone() }
+            }
+            ClauseLiteral::CompareVariables { lhs, comparator, rhs } => {
+                ClauseLiteral::CompareVariables { lhs: map(lhs), comparator: comparator.clone(), rhs: map(rhs) }
+            }
+            ClauseLiteral::CompareConstant { lhs, comparator, rhs } => {
+                ClauseLiteral::CompareConstant { lhs: map(lhs), comparator: comparator.clone(), rhs: rhs.clone() }
+            }
+            ClauseLiteral::Not { conjunction } => ClauseLiteral::Not { conjunction: map_all(conjunction) },
+            ClauseLiteral::Or { branches } => {
+                ClauseLiteral::Or { branches: branches.iter().map(|branch| map_all(branch)).collect() }
+            }
+            ClauseLiteral::Call { function, outputs } => {
+                ClauseLiteral::Call { function: function.clone(), outputs: outputs.iter().map(map).collect() }
+            }
         }
     }
 }
@@ -95,14 +189,34 @@ pub struct Clause {
     conjunction: Vec<ClauseLiteral>,
     // Contains possible types for each variable, based on the schema
     types_: HashMap<ClauseVariable, BTreeSet<SchemaType>>,
-    // value_types: HashMap<ClauseVariable, BTreeSet<typedb_driver::concept::ValueType>>,
+    // Value type of each attribute variable
+    value_types: HashMap<ClauseVariable, ValueType>,
+    // The variables whose bindings are the instances covered by the clause
+    head: Vec<ClauseVariable>,
 }
 
 // TODO: Lookahead
-// TODO: Unify existing variables rather than always introducing a new one
 impl Clause {
     pub fn new_empty() -> Clause {
-        Self { conjunction: Vec::new(), types_: HashMap::new() } // value_types: HashMap::new() }
+        Self {
+            conjunction: Vec::new(),
+            types_: HashMap::new(),
+            value_types: HashMap::new(),
+            head: vec![ClauseVariable::head(0)],
+        }
+    }
+
+    // `$instance isa T0; $instance_1 isa T1; ...` with one head variable per target type
+    pub fn new_from_head(types_: &[SchemaType], schema: &Schema) -> Self {
+        let mut clause = Self::new_empty();
+        clause.head = (0..types_.len()).map(ClauseVariable::head).collect();
+        types_.iter().enumerate().fold(clause, |clause, (position, type_)| {
+            clause.extend_with_isa(&ClauseVariable::head(position), type_, schema)
+        })
+    }
+
+    pub fn head(&self) -> &[ClauseVariable] {
+        &self.head
     }
 
     pub fn new_from_isa(type_: SchemaType, schema: &Schema) -> Self {
@@ -110,10 +224,112 @@ impl Clause {
         clause.extend_with_isa(&clause.fresh_variable(&type_, None), &type_, schema)
     }
 
+    // A single clause equivalent to the disjunction of the clauses. Shared prefixes are factored out,
+    // so a theory whose clauses all start with the same `isa` becomes one query with a disjunctive tail.
+    // Variables local to a branch of the disjunction are renamed, so that they are not bound together with those of
+    // another branch, and the merged clause only knows the types of the variables it binds outside the disjunction.
+    pub fn new_from_disjunction(clauses: &[Clause]) -> Option<Self> {
+        let first = clauses.first()?;
+        let conjunctions: Vec<&[ClauseLiteral]> = clauses.iter().map(|clause| clause.conjunction.as_slice()).collect();
+        let conjunction = factor_disjunction(&conjunctions, &first.head.iter().cloned().collect());
+        let bound: HashSet<&ClauseVariable> =
+            first.head.iter().chain(conjunction.iter().flat_map(|literal| literal.bound_variables())).collect();
+        let mut merged = Self {
+            types_: HashMap::new(),
+            value_types: HashMap::new(),
+            head: first.head.clone(),
+            conjunction: conjunction.clone(),
+        };
+        for clause in clauses {
+            for (var, types_) in clause.types_.iter().filter(|(var, _)| bound.contains(var)) {
+                merged.types_.entry(var.clone()).or_default().extend(types_.iter().cloned());
+            }
+            for (var, value_type) in clause.value_types.iter().filter(|(var, _)| bound.contains(var)) {
+                merged.value_types.entry(var.clone()).or_insert_with(|| value_type.clone());
+            }
+        }
+        Some(merged)
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.conjunction.len()
     }
 
+    pub(crate) fn literals(&self) -> &[ClauseLiteral] {
+        &self.conjunction
+    }
+
+    // The clause of the first `length` literals, with the types of variables as they were after adding them.
+    pub(crate) fn prefix(&self, length: usize, schema: &Schema) -> Clause {
+        let empty = Self {
+            conjunction: Vec::new(),
+            types_: HashMap::new(),
+            value_types: HashMap::new(),
+            head: self.head.clone(),
+        };
+        self.conjunction[..length]
+            .iter()
+            .fold(empty, |prefix, literal| prefix.extend_with_literal(literal, schema).unwrap_or(prefix))
+    }
+
+    pub(crate) fn binds(&self, var: &ClauseVariable) -> bool {
+        self.types_.contains_key(var)
+    }
+
+    // Whether the literal constrains a variable already in the clause, so adding it keeps the clause connected.
+    pub(crate) fn connects(&self, literal: &ClauseLiteral) -> bool {
+        match literal {
+            ClauseLiteral::Isa { instance, .. } => self.binds(instance),
+            ClauseLiteral::Has { owner, .. } | ClauseLiteral::HasValue { owner, .. } => self.binds(owner),
+            ClauseLiteral::Links { relation, player, .. } => self.binds(relation) || self.binds(player),
+            ClauseLiteral::CompareConstant { lhs, .. } => self.binds(lhs),
+            ClauseLiteral::CompareVariables { lhs, rhs, .. } => self.binds(lhs) && self.binds(rhs),
+            _ => false,
+        }
+    }
+
+    // Least general generalisation (Plotkin): every pair of compatible literals, one from each clause, is
+    // anti-unified. Each pair of variables becomes one variable, and each pair of types its nearest common supertype.
+    // Literals which are not connected to the head are dropped, since they do not constrain it.
+    pub fn lgg(&self, other: &Clause, schema: &Schema) -> Clause {
+        let mut pairs: HashMap<(ClauseVariable, ClauseVariable), ClauseVariable> = self
+            .head
+            .iter()
+            .zip(&other.head)
+            .map(|(first, second)| ((first.clone(), second.clone()), first.clone()))
+            .collect();
+        let mut literals = Vec::new();
+        for first in &self.conjunction {
+            for second in &other.conjunction {
+                if let Some(literal) = anti_unify(first, second, &mut pairs, schema)
+                    && !literals.contains(&literal)
+                {
+                    literals.push(literal);
+                }
+            }
+        }
+
+        let mut lgg = Self {
+            conjunction: Vec::new(),
+            types_: HashMap::new(),
+            value_types: HashMap::new(),
+            head: self.head.clone(),
+        };
+        // Head variables of a clause with no literals yet, e.g. Clause::new_empty, are untyped.
+        for var in &self.head {
+            if let Some(types_) = self.types_.get(var) {
+                lgg.update_types(var, types_.clone());
+            }
+        }
+        while let Some(index) = literals.iter().position(|literal| lgg.connects(literal)) {
+            let literal = literals.remove(index);
+            if let Some(extended) = lgg.extend_with_literal(&literal, schema) {
+                lgg = extended;
+            }
+        }
+        lgg
+    }
+
     pub fn refine_to_length(&self, language: &HypothesisLanguage, depth: usize) -> Vec<Clause> {
         let mut clauses = Vec::new();
         self._refine_to_length(language, depth, &mut clauses);
@@ -132,12 +348,42 @@ impl Clause {
         }
     }
 
+    // Each refinement once, since every one of them costs a coverage query.
     pub fn refine(&self, language: &HypothesisLanguage) -> Vec<Clause> {
+        let mut refinements = self.refine_positive(language);
+        if language.max_negation_length > 0 {
+            refinements.extend(self.negated_refinements(language));
+        }
+        // Skip refinements which only repeat literals of the clause, e.g. the same `has colour "red"`
+        refinements
+            .into_iter()
+            .filter(|clause| clause.conjunction[self.len()..].iter().any(|literal| !self.conjunction.contains(literal)))
+            .unique_by(|clause| clause.to_typeql())
+            .collect()
+    }
+
+    // Negates positive refinements of up to max_negation_length literals. Each length extends every refinement of the
+    // one before, so only max_negated_refinements of each are kept: the first by their TypeQL, to stay deterministic.
+    fn negated_refinements(&self, language: &HypothesisLanguage) -> Vec<Clause> {
+        let mut refinements = Vec::new();
+        let mut extended = vec![self.clone()];
+        for _ in 0..language.max_negation_length {
+            extended = extended
+                .iter()
+                .flat_map(|clause| clause.refine_positive(language))
+                .sorted_by_cached_key(|clause| clause.to_typeql())
+                .take(language.max_negated_refinements)
+                .collect();
+            refinements
+                .extend(extended.iter().map(|clause| self.extend_with_not(clause.conjunction[self.len()..].to_vec())));
+        }
+        refinements
+    }
+
+    fn refine_positive(&self, language: &HypothesisLanguage) -> Vec<Clause> {
         let mut refinements = Vec::new();
         let schema = &language.schema;
         // For each existing variable, generate refinements
-        // TODO: Unify vars in hypothesis
-        let existing_vars: Vec<_> = self.types_.keys().cloned().collect();
         for (var, possible_types) in self.types_.iter() {
             // 1. Add type constraints (Isa literals)
             if possible_types.len() > 1 {
@@ -146,11 +392,20 @@ impl Clause {
                 }
             }
 
-            // Attribute ownerships
-            #[cfg(FALSE)]
+            // Attribute ownerships, alone or compared against existing attribute variables
             for type_ in possible_types {
-                for attr_type in schema.owns.get(&type_).unwrap_or(&BTreeSet::new()) {
-                    refinements.push(self.extend_with_has(var, attr_type, schema));
+                for attr_type in schema.owns.get(type_).unwrap_or(&BTreeSet::new()) {
+                    if !language.comparable_attribute_types.contains(attr_type) {
+                        continue;
+                    }
+                    let refined = self.extend_with_has(var, attr_type, schema);
+                    let ClauseLiteral::Has { attribute, .. } = refined.conjunction.last().unwrap().clone() else {
+                        unreachable!()
+                    };
+                    refinements.extend(refined.comparison_refinements(&attribute, language));
+                    if !schema.numeric_attribute_thresholds.contains_key(attr_type) {
+                        refinements.push(refined); // Else added below
+                    }
                 }
             }
             #[cfg(FALSE)]
@@ -177,12 +432,33 @@ impl Clause {
                 }
             }
 
+            // Numeric attributes, compared against thresholds. Adding the attribute alone checks it exists.
+            for type_ in possible_types {
+                for attr_type in schema.owns.get(type_).unwrap_or(&BTreeSet::new()) {
+                    if let Some(thresholds) = schema.numeric_attribute_thresholds.get(attr_type) {
+                        let refined = self.extend_with_has(var, attr_type, schema);
+                        let ClauseLiteral::Has { attribute, .. } = refined.conjunction.last().unwrap().clone() else {
+                            unreachable!()
+                        };
+                        refinements.extend(refined.threshold_refinements(&attribute, thresholds));
+                        refinements.push(refined);
+                    }
+                }
+            }
+            for type_ in possible_types {
+                if let Some(thresholds) = schema.numeric_attribute_thresholds.get(type_) {
+                    refinements.extend(self.threshold_refinements(var, thresholds));
+                }
+            }
+
             // Relations we relate
             for type_ in possible_types {
                 for role_type in schema.relates.get(type_).unwrap_or(&BTreeSet::new()) {
                     refinements.push(self.extend_with_related_links(&var, role_type, schema));
+                    if language.unify_variables {
+                        refinements.extend(self.unified_related_links(var, role_type, schema));
+                    }
                 }
-                // TODO: Find types relating role, and see if existing vars can be used.
             }
 
             // Relations we play roles in
@@ -198,33 +474,72 @@ impl Clause {
                         for rel_type_ in refined.types_.get(&rel_var).unwrap() {
                             for other_role in schema.relates.get(rel_type_).unwrap_or(&BTreeSet::new()) {
                                 refinements.push(refined.extend_with_related_links(&rel_var, other_role, schema));
+                                if language.unify_variables {
+                                    refinements.extend(refined.unified_related_links(&rel_var, other_role, schema));
+                                }
                             }
                         }
                     }
+                    // Links to relations already in the clause come from their side, see unified_related_links.
                 }
-                // TODO: Find types playing role, and see if existing vars can be used.
             }
         }
 
-        #[cfg(FALSE)]
-        for i in 0..existing_vars.len() {
-            for j in (i + 1)..existing_vars.len() {
-                let var1 = &existing_vars[i];
-                let var2 = &existing_vars[j];
-                if let (Some(types1), Some(types2)) = (self.types_.get(var1), self.types_.get(var2)) {
-                    if types1.intersection(types2).count() > 0 {
-                        for comparator in ValueComparator::VALUES {
-                            refinements.push(self.extend_with_comparison(var1, comparator, var2, schema));
-                        }
-                    }
-                }
+        if !language.comparable_attribute_types.is_empty() {
+            for (lhs, rhs) in self.value_types.keys().sorted().tuple_combinations() {
+                refinements.extend(self.comparisons(lhs, rhs, language));
             }
         }
-        // TODO: Add comparison against value
 
         refinements
     }
 
+    // Compares the attribute variable against every other attribute variable.
+    fn comparison_refinements(&self, attr_var: &ClauseVariable, language: &HypothesisLanguage) -> Vec<Clause> {
+        self.value_types
+            .keys()
+            .filter(|other| *other != attr_var)
+            .flat_map(|other| self.comparisons(attr_var, other, language))
+            .collect()
+    }
+
+    // `==`, `!=` and `<` in both directions, if both variables are of comparable attribute types (not only used for
+    // thresholds) with the same value type. Comparisons already in the clause are skipped.
+    fn comparisons(&self, lhs: &ClauseVariable, rhs: &ClauseVariable, language: &HypothesisLanguage) -> Vec<Clause> {
+        let (Some(lhs_type), Some(rhs_type)) = (self.value_types.get(lhs), self.value_types.get(rhs)) else {
+            return Vec::new();
+        };
+        let is_comparable = |var: &ClauseVariable| {
+            self.types_.get(var).is_some_and(|types_| !types_.is_disjoint(&language.comparable_attribute_types))
+        };
+        if lhs_type != rhs_type
+            || matches!(lhs_type, ValueType::Struct(_))
+            || !is_comparable(lhs)
+            || !is_comparable(rhs)
+        {
+            return Vec::new();
+        }
+        let mut comparisons = vec![(lhs, ValueComparator::Eq, rhs), (lhs, ValueComparator::Neq, rhs)];
+        if is_ordered(lhs_type) {
+            comparisons.push((lhs, ValueComparator::Lt, rhs));
+            comparisons.push((rhs, ValueComparator::Lt, lhs));
+        }
+        let exists = |(lhs, comparator, rhs): &(&ClauseVariable, ValueComparator, &ClauseVariable)| {
+            let symmetric = matches!(comparator, ValueComparator::Eq | ValueComparator::Neq);
+            self.conjunction.iter().any(|literal| match literal {
+                ClauseLiteral::CompareVariables { lhs: l, comparator: c, rhs: r } => {
+                    c == comparator && ((l == *lhs && r == *rhs) || (symmetric && l == *rhs && r == *lhs))
+                }
+                _ => false,
+            })
+        };
+        comparisons
+            .into_iter()
+            .filter(|comparison| !exists(comparison))
+            .map(|(lhs, comparator, rhs)| self.extend_with_comparison(lhs, comparator, rhs))
+            .collect()
+    }
+
     pub(crate) fn extend_with_isa(&self, var: &ClauseVariable, type_: &SchemaType, schema: &Schema) -> Clause {
         let mut new_clause = self.clone();
         new_clause.conjunction.push(ClauseLiteral::Isa { instance: var.clone(), type_: type_.clone() });
@@ -249,6 +564,9 @@ impl Clause {
         // TODO: Do I have to add subtypes?
         let attr_types = BTreeSet::from([attr_type.clone()]);
         new_clause.update_types(&attr_var, attr_types);
+        if let Some(value_type) = attr_type.value_type() {
+            new_clause.value_types.insert(attr_var.clone(), value_type.clone());
+        }
         let owner_types = schema.owners.get(&attr_type).unwrap().clone();
         new_clause.update_types(&owner, owner_types);
         new_clause
@@ -276,16 +594,45 @@ impl Clause {
         &self,
         attr_var: &ClauseVariable,
         value: &typedb_driver::concept::value::Value,
+    ) -> Clause {
+        self.extend_with_constant_comparison(attr_var, ValueComparator::Eq, value)
+    }
+
+    pub(crate) fn extend_with_constant_comparison(
+        &self,
+        attr_var: &ClauseVariable,
+        comparator: ValueComparator,
+        value: &typedb_driver::concept::value::Value,
     ) -> Clause {
         let mut new_clause = self.clone();
         new_clause.conjunction.push(ClauseLiteral::CompareConstant {
             lhs: attr_var.clone(),
-            comparator: ValueComparator::Eq,
+            comparator,
             rhs: value.clone(),
         });
         new_clause
     }
 
+    // `<= t` and `> t` for each threshold, which split the values exactly (see language::numeric_thresholds).
+    // Comparisons already in the clause are skipped.
+    fn threshold_refinements(
+        &self,
+        attr_var: &ClauseVariable,
+        thresholds: &[typedb_driver::concept::value::Value],
+    ) -> Vec<Clause> {
+        thresholds
+            .iter()
+            .cartesian_product([ValueComparator::Lte, ValueComparator::Gt])
+            .filter(|(threshold, comparator)| {
+                !self.conjunction.iter().any(|literal| {
+                    matches!(literal, ClauseLiteral::CompareConstant { lhs, comparator: c, rhs }
+                        if lhs == attr_var && c == comparator && rhs == *threshold)
+                })
+            })
+            .map(|(threshold, comparator)| self.extend_with_constant_comparison(attr_var, comparator, threshold))
+            .collect()
+    }
+
     pub(crate) fn extend_with_played_links(
         &self,
         player: &ClauseVariable,
@@ -322,6 +669,104 @@ impl Clause {
         new_clause
     }
 
+    // The types of variables are not narrowed by the negated literals
+    pub(crate) fn extend_with_not(&self, conjunction: Vec<ClauseLiteral>) -> Clause {
+        let mut new_clause = self.clone();
+        new_clause.conjunction.push(ClauseLiteral::Not { conjunction });
+        new_clause
+    }
+
+    // None if the call is already present
+    pub(crate) fn extend_with_call(&self, function: &str, outputs: Vec<ClauseVariable>) -> Option<Clause> {
+        let literal = ClauseLiteral::Call { function: function.to_owned(), outputs };
+        if self.conjunction.contains(&literal) {
+            return None;
+        }
+        let mut new_clause = self.clone();
+        new_clause.conjunction.push(literal);
+        Some(new_clause)
+    }
+
+    pub(crate) fn calls(&self, function: &str) -> bool {
+        self.conjunction
+            .iter()
+            .any(|literal| matches!(literal, ClauseLiteral::Call { function: f, .. } if f == function))
+    }
+
+    // Adds a literal over given variables, e.g. one taken from a bottom clause, narrowing their types like the
+    // other extend_with_ methods. None if the literal is already present.
+    pub(crate) fn extend_with_literal(&self, literal: &ClauseLiteral, schema: &Schema) -> Option<Clause> {
+        if self.conjunction.contains(literal) {
+            return None;
+        }
+        let mut new_clause = self.clone();
+        new_clause.conjunction.push(literal.clone());
+        match literal {
+            ClauseLiteral::Isa { instance, type_ } => {
+                new_clause.update_types(instance, schema.subtypes[type_].clone());
+            }
+            ClauseLiteral::Has { owner, type_, attribute } => {
+                new_clause.update_types(attribute, BTreeSet::from([type_.clone()]));
+                if let Some(value_type) = type_.value_type() {
+                    new_clause.value_types.insert(attribute.clone(), value_type.clone());
+                }
+                new_clause.update_types(owner, schema.owners[type_].clone());
+            }
+            ClauseLiteral::HasValue { owner, type_, .. } => {
+                new_clause.update_types(owner, schema.owners[type_].clone());
+            }
+            ClauseLiteral::Links { relation, role, player } => {
+                new_clause.update_types(relation, schema.related_by[role].clone());
+                new_clause.update_types(player, schema.players[role].clone());
+            }
+            _ => {}
+        }
+        Some(new_clause)
+    }
+
+    // Links two variables already in the clause. None if the literal is already present.
+    pub(crate) fn extend_with_links(
+        &self,
+        relation: &ClauseVariable,
+        role_type: &SchemaType,
+        player: &ClauseVariable,
+        schema: &Schema,
+    ) -> Option<Clause> {
+        let exists = self.conjunction.iter().any(|literal| match literal {
+            ClauseLiteral::Links { relation: r, role, player: p } => r == relation && role == role_type && p == player,
+            _ => false,
+        });
+        if exists {
+            return None;
+        }
+        let mut new_clause = self.clone();
+        new_clause.conjunction.push(ClauseLiteral::Links {
+            relation: relation.clone(),
+            role: role_type.clone(),
+            player: player.clone(),
+        });
+        new_clause.update_types(relation, schema.related_by[role_type].clone());
+        new_clause.update_types(player, schema.players[role_type].clone());
+        Some(new_clause)
+    }
+
+    // Like extend_with_related_links, but binds the player to each existing variable that could play the role.
+    fn unified_related_links(&self, relation: &ClauseVariable, role_type: &SchemaType, schema: &Schema) -> Vec<Clause> {
+        let player_types = schema.players.get(role_type).cloned().unwrap_or_default();
+        self.unifiable_variables(&player_types)
+            .filter(|player| *player != relation)
+            .filter_map(|player| self.extend_with_links(relation, role_type, player, schema))
+            .collect()
+    }
+
+    // Existing variables which may take one of the given types
+    pub(crate) fn unifiable_variables<'a>(
+        &'a self,
+        types_: &'a BTreeSet<SchemaType>,
+    ) -> impl Iterator<Item = &'a ClauseVariable> + 'a {
+        self.types_.iter().filter(move |(_, var_types)| !var_types.is_disjoint(types_)).map(|(var, _)| var)
+    }
+
     pub(crate) fn extend_with_comparison(
         &self,
         var1: &ClauseVariable,
@@ -338,10 +783,12 @@ impl Clause {
     }
 
     fn fresh_variable(&self, type_: &SchemaType, suffix_opt: Option<&str>) -> ClauseVariable {
+        // Count nested literals too, so variables local to a negation are never reused outside it.
+        let position = self.conjunction.iter().map(|literal| literal.size()).sum::<usize>();
         let name = if let Some(suffix) = suffix_opt {
-            format!("{}_{}_{}", type_.label().replace(":", "__"), self.conjunction.len(), suffix)
+            format!("{}_{}_{}", type_.label().replace(":", "__"), position, suffix)
         } else {
-            format!("{}_{}", type_.label().replace(":", "__"), self.conjunction.len())
+            format!("{}_{}", type_.label().replace(":", "__"), position)
         };
         ClauseVariable(name)
     }
@@ -350,6 +797,80 @@ impl Clause {
         self.conjunction.iter().map(|literal| literal.to_typeql()).join(";\n")
     }
 
+    // The literals from `start` onwards, e.g. those a refinement added to a shorter clause.
+    pub(crate) fn to_typeql_from(&self, start: usize) -> String {
+        self.conjunction[start..].iter().map(|literal| literal.to_typeql()).join(";\n")
+    }
+
+    // The clause with a test that failed, i.e. that no binding of the test's variables other than the head satisfies.
+    // Those variables get the suffix, so that they stay local to the negation even where this clause reuses their names.
+    pub(crate) fn extend_with_failed_test(&self, test: &Clause, suffix: &str) -> Clause {
+        let rename = |var: &ClauseVariable| match test.head.contains(var) {
+            true => var.clone(),
+            false => ClauseVariable(format!("{}_{suffix}", var.name())),
+        };
+        self.extend_with_not(test.conjunction.iter().map(|literal| literal.map_variables(&rename)).collect())
+    }
+
+    // The clause with its variables other than the head suffixed, so that branches of a disjunction which happen to
+    // share a variable name do not join on it.
+    pub(crate) fn with_suffixed_variables(&self, suffix: &str) -> Clause {
+        let rename = |var: &ClauseVariable| match self.head.contains(var) {
+            true => var.clone(),
+            false => ClauseVariable(format!("{}_{suffix}", var.name())),
+        };
+        Self {
+            conjunction: self.conjunction.iter().map(|literal| literal.map_variables(&rename)).collect(),
+            types_: self.types_.iter().map(|(var, types_)| (rename(var), types_.clone())).collect(),
+            value_types: self.value_types.iter().map(|(var, value_type)| (rename(var), value_type.clone())).collect(),
+            head: self.head.clone(),
+        }
+    }
+
+    // The clause with one literal dropped, along with later literals which then no longer connect to the clause,
+    // e.g. comparisons of the attribute it bound. The types of variables are given by the remaining literals.
+    // Negations, disjunctions and calls are dropped if they mention a variable of the clause which is no longer bound:
+    // inside them, it would become local, and the clause would not be a generalisation.
+    pub fn without(&self, index: usize, schema: &Schema) -> Clause {
+        self.conjunction[index + 1..].iter().fold(self.prefix(index, schema), |clause, literal| {
+            let keep = match literal {
+                ClauseLiteral::Not { .. } | ClauseLiteral::Or { .. } | ClauseLiteral::Call { .. } => {
+                    literal.variables().into_iter().all(|var| !self.binds(var) || clause.binds(var))
+                }
+                _ => clause.connects(literal),
+            };
+            match keep {
+                true => clause.extend_with_literal(literal, schema).unwrap_or(clause),
+                false => clause,
+            }
+        })
+    }
+
+    // TypeQL which is the same for clauses that only differ in the order of their literals, or in the names of
+    // variables other than the head. Literals are sorted with those variables hidden, then the variables are renamed
+    // in the order they appear, and the literals sorted again.
+    pub(crate) fn canonical_typeql(&self) -> String {
+        let hidden = |var: &ClauseVariable| match self.head.contains(var) {
+            true => var.clone(),
+            false => ClauseVariable("_".to_owned()),
+        };
+        let sorted = self.conjunction.iter().sorted_by_cached_key(|literal| literal.map_variables(&hidden).to_typeql());
+        let names: RefCell<HashMap<ClauseVariable, ClauseVariable>> = RefCell::new(HashMap::new());
+        let rename = |var: &ClauseVariable| {
+            if self.head.contains(var) {
+                return var.clone();
+            }
+            let mut names = names.borrow_mut();
+            let next = ClauseVariable(format!("var_{}", names.len()));
+            names.entry(var.clone()).or_insert(next).clone()
+        };
+        sorted.map(|literal| literal.map_variables(&rename).to_typeql()).sorted().join(";\n")
+    }
+
+    pub(crate) fn is_prefix_of(&self, other: &Clause) -> bool {
+        other.conjunction.starts_with(&self.conjunction)
+    }
+
     pub fn fmt_with_indent(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
         let indent = INDENT.repeat(depth);
         let newline_indent = format!("\n{}", indent);
@@ -365,6 +886,120 @@ impl Clause {
     }
 }
 
+// Factors out the longest common prefix, then groups the remaining tails by their first literal. In each branch of
+// the resulting disjunction, the variables not bound before it are suffixed with the index of the branch.
+fn factor_disjunction(conjunctions: &[&[ClauseLiteral]], bound: &HashSet<ClauseVariable>) -> Vec<ClauseLiteral> {
+    if conjunctions.iter().any(|conjunction| conjunction.is_empty()) {
+        return Vec::new(); // One branch is always satisfied
+    }
+    if conjunctions.len() == 1 {
+        return conjunctions[0].to_vec();
+    }
+    let prefix_length = (0..conjunctions.iter().map(|conjunction| conjunction.len()).min().unwrap())
+        .take_while(|i| conjunctions.iter().all(|conjunction| conjunction[*i] == conjunctions[0][*i]))
+        .count();
+    if prefix_length > 0 {
+        let prefix = &conjunctions[0][..prefix_length];
+        let mut bound = bound.clone();
+        bound.extend(prefix.iter().flat_map(|literal| literal.bound_variables()).cloned());
+        let tails: Vec<_> = conjunctions.iter().map(|conjunction| &conjunction[prefix_length..]).collect();
+        let mut factored = prefix.to_vec();
+        factored.extend(factor_disjunction(&tails, &bound));
+        return factored;
+    }
+    let mut groups: Vec<Vec<&[ClauseLiteral]>> = Vec::new();
+    for conjunction in conjunctions {
+        match groups.iter_mut().find(|group| group[0][0] == conjunction[0]) {
+            Some(group) => group.push(conjunction),
+            None => groups.push(vec![conjunction]),
+        }
+    }
+    let branches = groups
+        .iter()
+        .enumerate()
+        .map(|(i, group)| {
+            let rename = |var: &ClauseVariable| match bound.contains(var) {
+                true => var.clone(),
+                false => ClauseVariable(format!("{}_or{i}", var.name())),
+            };
+            factor_disjunction(group, bound).iter().map(|literal| literal.map_variables(&rename)).collect()
+        })
+        .collect();
+    vec![ClauseLiteral::Or { branches }]
+}
+
+// The generalisation of two literals of the same kind, or None if they are incompatible.
+fn anti_unify(
+    first: &ClauseLiteral,
+    second: &ClauseLiteral,
+    pairs: &mut HashMap<(ClauseVariable, ClauseVariable), ClauseVariable>,
+    schema: &Schema,
+) -> Option<ClauseLiteral> {
+    let mut pair = |first: &ClauseVariable, second: &ClauseVariable| {
+        let fresh = ClauseVariable(format!("lgg_{}", pairs.len()));
+        pairs.entry((first.clone(), second.clone())).or_insert(fresh).clone()
+    };
+    match (first, second) {
+        (ClauseLiteral::Isa { instance: i1, type_: t1 }, ClauseLiteral::Isa { instance: i2, type_: t2 }) => {
+            Some(ClauseLiteral::Isa { type_: nearest_common_supertype(t1, t2, schema)?, instance: pair(i1, i2) })
+        }
+        (
+            ClauseLiteral::Has { owner: o1, type_: t1, attribute: a1 },
+            ClauseLiteral::Has { owner: o2, type_: t2, attribute: a2 },
+        ) if t1 == t2 => Some(ClauseLiteral::Has { owner: pair(o1, o2), type_: t1.clone(), attribute: pair(a1, a2) }),
+        // Different values generalise to an attribute variable.
+        (
+            ClauseLiteral::HasValue { owner: o1, type_: t1, value: v1 },
+            ClauseLiteral::HasValue { owner: o2, type_: t2, value: v2 },
+        ) if t1 == t2 => {
+            let owner = pair(o1, o2);
+            if v1 == v2 {
+                Some(ClauseLiteral::HasValue { owner, type_: t1.clone(), value: v1.clone() })
+            } else {
+                let attribute = pair(&ClauseVariable(format!("{t1} {v1}")), &ClauseVariable(format!("{t2} {v2}")));
+                Some(ClauseLiteral::Has { owner, type_: t1.clone(), attribute })
+            }
+        }
+        (
+            ClauseLiteral::Links { relation: r1, role: role1, player: p1 },
+            ClauseLiteral::Links { relation: r2, role: role2, player: p2 },
+        ) if role1 == role2 => {
+            Some(ClauseLiteral::Links { relation: pair(r1, r2), role: role1.clone(), player: pair(p1, p2) })
+        }
+        (
+            ClauseLiteral::CompareConstant { lhs: l1, comparator: c1, rhs: v1 },
+            ClauseLiteral::CompareConstant { lhs: l2, comparator: c2, rhs: v2 },
+        ) if c1 == c2 && v1 == v2 => {
+            Some(ClauseLiteral::CompareConstant { lhs: pair(l1, l2), comparator: c1.clone(), rhs: v1.clone() })
+        }
+        (
+            ClauseLiteral::CompareVariables { lhs: l1, comparator: c1, rhs: r1 },
+            ClauseLiteral::CompareVariables { lhs: l2, comparator: c2, rhs: r2 },
+        ) if c1 == c2 => {
+            Some(ClauseLiteral::CompareVariables { lhs: pair(l1, l2), comparator: c1.clone(), rhs: pair(r1, r2) })
+        }
+        _ => None,
+    }
+}
+
+// The supertype of both with the fewest subtypes. None if the schema has no common supertype.
+fn nearest_common_supertype(first: &SchemaType, second: &SchemaType, schema: &Schema) -> Option<SchemaType> {
+    if first == second {
+        return Some(first.clone());
+    }
+    schema
+        .subtypes
+        .iter()
+        .filter(|(_, subtypes)| subtypes.contains(first) && subtypes.contains(second))
+        .min_by(|(t1, s1), (t2, s2)| s1.len().cmp(&s2.len()).then_with(|| t1.cmp(t2)))
+        .map(|(type_, _)| type_.clone())
+}
+
+// Whether values of the type can be compared with `<`. Booleans are only compared with `==` and `!=`.
+fn is_ordered(value_type: &ValueType) -> bool {
+    !matches!(value_type, ValueType::Boolean | ValueType::Struct(_))
+}
+
 impl std::fmt::Display for Clause {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.to_typeql().as_str())
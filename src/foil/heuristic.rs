@@ -0,0 +1,79 @@
+use std::fmt::Debug;
+
+// Positive and negative examples covered by a clause
+#[derive(Debug, Clone, Copy)]
+pub struct Coverage {
+    pub positives: f64,
+    pub negatives: f64,
+}
+
+impl Coverage {
+    pub fn precision(&self) -> f64 {
+        self.positives / (self.positives + self.negatives)
+    }
+}
+
+// Scores a refinement of a clause. Higher is better. `total` is every target example, i.e. what the initial
+// clause covers, and `length` is the number of literals in the refinement.
+pub trait ClauseHeuristic: Debug + Send + Sync {
+    fn score(&self, parent: Coverage, refined: Coverage, total: Coverage, length: usize) -> f64;
+}
+
+// Quinlan's information gain: the positives still covered, times the bits saved encoding that one is positive.
+#[derive(Debug, Clone, Copy)]
+pub struct FoilGain;
+
+impl ClauseHeuristic for FoilGain {
+    fn score(&self, parent: Coverage, refined: Coverage, _total: Coverage, _length: usize) -> f64 {
+        if refined.positives == 0.0 || parent.positives == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        refined.positives * (refined.precision().log2() - parent.precision().log2())
+    }
+}
+
+// Precision with one imaginary positive and one imaginary negative, so rarely covering clauses are not favoured.
+#[derive(Debug, Clone, Copy)]
+pub struct LaplaceAccuracy;
+
+impl ClauseHeuristic for LaplaceAccuracy {
+    fn score(&self, _parent: Coverage, refined: Coverage, _total: Coverage, _length: usize) -> f64 {
+        (refined.positives + 1.0) / (refined.positives + refined.negatives + 2.0)
+    }
+}
+
+// Precision with m imaginary examples distributed like the target examples. Larger m tolerates more noise.
+#[derive(Debug, Clone, Copy)]
+pub struct MEstimate {
+    pub m: f64,
+}
+
+impl ClauseHeuristic for MEstimate {
+    fn score(&self, _parent: Coverage, refined: Coverage, total: Coverage, _length: usize) -> f64 {
+        (refined.positives + self.m * total.precision()) / (refined.positives + refined.negatives + self.m)
+    }
+}
+
+// Weighted relative accuracy: the gain in precision over the target examples, weighted by the fraction covered.
+#[derive(Debug, Clone, Copy)]
+pub struct WeightedRelativeAccuracy;
+
+impl ClauseHeuristic for WeightedRelativeAccuracy {
+    fn score(&self, _parent: Coverage, refined: Coverage, total: Coverage, _length: usize) -> f64 {
+        let covered = refined.positives + refined.negatives;
+        if covered == 0.0 {
+            return 0.0;
+        }
+        covered / (total.positives + total.negatives) * (refined.precision() - total.precision())
+    }
+}
+
+// Progol's compression: positives covered, less the negatives covered and the literals needed to describe them.
+#[derive(Debug, Clone, Copy)]
+pub struct Compression;
+
+impl ClauseHeuristic for Compression {
+    fn score(&self, _parent: Coverage, refined: Coverage, _total: Coverage, length: usize) -> f64 {
+        refined.positives - refined.negatives - length as f64
+    }
+}
@@ -1,37 +1,136 @@
-use std::collections::{BTreeSet, HashSet};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
 
-use typedb_driver::{Promise, TransactionType, concept::Concept};
+use itertools::Itertools;
+use typedb_driver::{
+    Promise, TransactionType,
+    concept::{Concept, Value},
+};
 
 use crate::{
-    Instance, TypeDBHelper,
-    clause::{Clause, ClauseVariable},
+    CoverageOracle, Instance, TypeDBHelper, class_label,
+    clause::{Clause, ClauseLiteral, ClauseVariable},
+    foil::{
+        heuristic::{ClauseHeuristic, Coverage, FoilGain},
+        stopping::StoppingCriterion,
+    },
     language::{HypothesisLanguage, SchemaType},
 };
 
+pub mod heuristic;
+mod pruning;
+pub mod stopping;
+
 type FoilExample = Instance;
 
+// Settings of the FOIL search. The defaults are those FOIL has always used.
+#[derive(Debug, Clone)]
+pub struct FoilConfig {
+    pub max_theory_length: usize, // Clauses learnt before giving up on covering every positive
+    pub max_clause_length: usize, // Literals in a clause, including those of the initial clause
+    pub lookahead_one: bool,      // Also consider refinements of two literals at each step
+    pub beam_width: usize,        // Clauses kept at each step of learning a clause. 1 is FOIL's hill-climbing.
+    pub heuristic: Arc<dyn ClauseHeuristic>, // Scores refinements. FOIL gain by default.
+    pub stopping_criteria: Vec<StoppingCriterion>, // For noisy data. By default, clauses grow until they are pure.
+    pub max_relation_negatives: Option<usize>, // Negatives read by discover_relation. All of them by default.
+    // Allow clauses to call the theory learnt so far, as the TypeQL function `predicted_<target>`.
+    pub recursive: bool,
+}
+
+impl Default for FoilConfig {
+    fn default() -> Self {
+        Self {
+            max_theory_length: 20,
+            max_clause_length: 10,
+            lookahead_one: false,
+            beam_width: 1,
+            heuristic: Arc::new(FoilGain),
+            stopping_criteria: Vec::new(),
+            max_relation_negatives: None,
+            recursive: false,
+        }
+    }
+}
+
+impl FoilConfig {
+    pub fn with_max_theory_length(self, max_theory_length: usize) -> Self {
+        Self { max_theory_length, ..self }
+    }
+
+    pub fn with_max_clause_length(self, max_clause_length: usize) -> Self {
+        Self { max_clause_length, ..self }
+    }
+
+    pub fn with_lookahead_one(self, lookahead_one: bool) -> Self {
+        Self { lookahead_one, ..self }
+    }
+
+    pub fn with_beam_width(self, beam_width: usize) -> Self {
+        Self { beam_width, ..self }
+    }
+
+    pub fn with_heuristic(self, heuristic: impl ClauseHeuristic + 'static) -> Self {
+        Self { heuristic: Arc::new(heuristic), ..self }
+    }
+
+    pub fn with_stopping_criterion(mut self, criterion: StoppingCriterion) -> Self {
+        self.stopping_criteria.push(criterion);
+        self
+    }
+
+    pub fn with_max_relation_negatives(self, max_relation_negatives: usize) -> Self {
+        Self { max_relation_negatives: Some(max_relation_negatives), ..self }
+    }
+
+    pub fn with_recursive(self, recursive: bool) -> Self {
+        Self { recursive, ..self }
+    }
+}
+
+// A clause in the beam, with the target examples it covers
+#[derive(Clone)]
+struct BeamEntry {
+    clause: Clause,
+    positives: HashSet<FoilExample>,
+    negatives: HashSet<FoilExample>,
+    bits: f64, // To encode the literals added to the initial clause, each chosen among the refinements
+}
+
+impl BeamEntry {
+    fn coverage(&self) -> Coverage {
+        Coverage { positives: self.positives.len() as f64, negatives: self.negatives.len() as f64 }
+    }
+}
+
 // Ok it's not a foil task, but I have no time.
-pub struct FoilLearningTask {
-    pub typedb: TypeDBHelper,
+// Clauses are tested against TypeDB, or any other CoverageOracle such as crate::memory::FactStore.
+pub struct FoilLearningTask<O: CoverageOracle = TypeDBHelper> {
+    pub oracle: O,
 
-    pub target_type: SchemaType,       // Label of the type. Used for initial clause.
-    pub class_attribute_label: String, // Label of the class attribute
+    pub target_type: SchemaType,     // The type being learnt. Names the theory function.
+    pub head_types: Vec<SchemaType>, // Type of each head variable. Used for initial clause.
+    pub class_attribute_label: Option<String>, // Label of the class attribute, unless learning a relation
     pub language: HypothesisLanguage,
     pub positive_examples: HashSet<FoilExample>,
     pub negative_examples: HashSet<FoilExample>,
+    // Examples by the label of their class (see crate::class_label). Empty when learning a relation.
+    pub examples_by_class: HashMap<String, HashSet<FoilExample>>,
+    // The class of the positive examples, see set_positive_class. None when learning a relation.
+    pub positive_class: Option<String>,
+    pub config: FoilConfig,
 }
 
 impl FoilLearningTask {
     const CLASS_VAR_NAME: &'static str = "class_0";
-    const MAX_THEORY_LENGTH: usize = 20;
-    const MAX_CLAUSE_LENGTH: usize = 10;
-    const LOOKAHEAD_ONE: bool = false;
 
     pub fn discover(
         typedb: TypeDBHelper,
         language: HypothesisLanguage,
         target_type_label: String,
         class_attribute_label: String,
+        config: FoilConfig,
     ) -> Result<Self, typedb_driver::Error> {
         let query = format!(
             "match ${} isa {}, has {} ${};",
@@ -49,56 +148,235 @@ impl FoilLearningTask {
                 let row = row_result?;
                 Ok::<_, typedb_driver::Error>((
                     row.get(ClauseVariable::INSTANCE_VAR_NAME).unwrap().unwrap().clone(),
-                    row.get(Self::CLASS_VAR_NAME)
-                        .unwrap()
-                        .unwrap()
-                        .try_get_boolean()
-                        .expect("Expected class attribute to be boolean for FOIL tasks"),
+                    row.get(Self::CLASS_VAR_NAME).unwrap().unwrap().try_get_value().unwrap().clone(),
                 ))
             })
-            .collect::<Result<Vec<(Concept, bool)>, _>>()?;
+            .collect::<Result<Vec<(Concept, Value)>, _>>()?;
+        let mut examples_by_class: HashMap<String, HashSet<FoilExample>> = HashMap::new();
+        dataset.iter().for_each(|(concept, class)| {
+            examples_by_class.entry(class_label(class)).or_default().insert(concept.into());
+        });
+        Self::from_examples(typedb, language, target_type_label, class_attribute_label, examples_by_class, config)
+    }
+
+    // Learns which tuples of players are related by the relation type, e.g. `(employee, employer)` in employment.
+    // Each role is paired with the type of its head variable. Tuples which are not related are negative examples (closed world),
+    // at most config.max_relation_negatives of them.
+    // The relation itself is removed from the hypothesis language.
+    pub fn discover_relation(
+        typedb: TypeDBHelper,
+        language: HypothesisLanguage,
+        relation_label: String,
+        role_players: Vec<(String, String)>,
+        config: FoilConfig,
+    ) -> Result<Self, typedb_driver::Error> {
+        let lookup = |label: &str| language.lookup_type(label).expect("Expected type to be in schema.subtypes");
+        let target_type = lookup(&relation_label);
+        let head_types: Vec<SchemaType> = role_players.iter().map(|(_, player_label)| lookup(player_label)).collect();
+        let head = (0..role_players.len()).map(ClauseVariable::head).collect::<Vec<_>>();
+        let role_links = role_players.iter().zip(&head).map(|((role, _), var)| format!("{role}: {var}")).join(", ");
+        let head_isa = head_types.iter().zip(&head).map(|(type_, var)| format!("{var} isa {type_};")).join(" ");
+        let select = head.iter().join(", ");
+
+        let tx = typedb.read_tx()?;
+        let read_tuples = |query: String| {
+            tx.query(query)
+                .resolve()?
+                .into_rows()
+                .map(|row| {
+                    let row = row?;
+                    Ok(Instance::from_concepts(head.iter().map(|var| row.get(var.name()).unwrap().unwrap())))
+                })
+                .collect::<Result<HashSet<FoilExample>, typedb_driver::Error>>()
+        };
         let positive_examples =
-            dataset.iter().filter_map(|(concept, is_positive)| is_positive.then_some(concept.into())).collect();
-        let negative_examples =
-            dataset.iter().filter_map(|(concept, is_positive)| (!is_positive).then_some(concept.into())).collect();
-        let target_type = language
-            .schema
-            .subtypes
-            .keys()
-            .find(|t| t.label() == target_type_label)
-            .expect("Expected target_type to be in schema.subtypes")
-            .clone();
-        Ok(Self { typedb, class_attribute_label, target_type, language, positive_examples, negative_examples })
+            read_tuples(format!("match {head_isa} {relation_label} ({role_links}); select {select};"))?;
+        // Closed world: every tuple of players not in the relation is a negative. There are as many as
+        // the product of the players' counts, so max_relation_negatives caps how many are read.
+        let limit = config.max_relation_negatives.map(|max| format!(" limit {max};")).unwrap_or_default();
+        let negative_examples = read_tuples(format!(
+            "match {head_isa} not {{ {relation_label} ({role_links}); }}; select {select};{limit}"
+        ))?;
+
+        Ok(Self::relation_task(typedb, language, target_type, head_types, positive_examples, negative_examples, config))
+    }
+
+    // Defines the theory as a function in the database, see theory_definition.
+    pub fn define_theory(&self, theory: &[Clause]) -> Result<(), typedb_driver::Error> {
+        let definition = self
+            .theory_definition(theory)
+            .ok_or_else(|| typedb_driver::Error::Other("Cannot define an empty theory".to_owned()))?;
+        self.oracle.define(&definition)
+    }
+}
+
+impl<O: CoverageOracle> FoilLearningTask<O> {
+    // A task over examples read elsewhere, by the label of their class (see crate::class_label).
+    // As in discover, the positive class is set when the classes are booleans.
+    pub fn from_examples(
+        oracle: O,
+        language: HypothesisLanguage,
+        target_type_label: String,
+        class_attribute_label: String,
+        examples_by_class: HashMap<String, HashSet<FoilExample>>,
+        config: FoilConfig,
+    ) -> Result<Self, typedb_driver::Error> {
+        if examples_by_class.is_empty() {
+            return Err(typedb_driver::Error::Other(format!("No examples of {target_type_label} to learn from")));
+        }
+        let target_type =
+            language.lookup_type(&target_type_label).expect("Expected target_type to be in schema.subtypes");
+        let boolean = examples_by_class.keys().all(|class| class == "true" || class == "false");
+        let mut task = Self {
+            oracle,
+            class_attribute_label: Some(class_attribute_label),
+            head_types: vec![target_type.clone()],
+            target_type,
+            language,
+            positive_examples: HashSet::new(),
+            negative_examples: HashSet::new(),
+            examples_by_class,
+            positive_class: None,
+            config,
+        };
+        // For other class attributes, choose the class with set_positive_class or use search_one_vs_rest
+        if boolean {
+            task.set_positive_class(&true.to_string());
+        }
+        Ok(task)
     }
 
-    pub fn deconstruct(self) -> TypeDBHelper {
-        self.typedb
+    // Like discover_relation, with the examples found by the oracle. All the unrelated tuples are read, and the
+    // first config.max_relation_negatives of them by IID are kept.
+    pub fn from_relation(
+        oracle: O,
+        language: HypothesisLanguage,
+        relation_label: String,
+        role_players: Vec<(String, String)>,
+        config: FoilConfig,
+    ) -> Result<Self, typedb_driver::Error> {
+        let lookup = |label: &str| language.lookup_type(label).expect("Expected type to be in schema.subtypes");
+        let target_type = lookup(&relation_label);
+        let head_types: Vec<SchemaType> = role_players.iter().map(|(_, player_label)| lookup(player_label)).collect();
+        let schema = &language.schema;
+        let players = Clause::new_from_head(&head_types, schema);
+        let relation = ClauseVariable(format!("{relation_label}_rel"));
+        let mut related = vec![ClauseLiteral::Isa { instance: relation.clone(), type_: target_type.clone() }];
+        related.extend(role_players.iter().zip(players.head()).map(|((role, _), player)| ClauseLiteral::Links {
+            relation: relation.clone(),
+            role: SchemaType::role(&format!("{relation_label}:{role}")),
+            player: player.clone(),
+        }));
+        let mut positive_clause = players.clone();
+        for literal in &related {
+            positive_clause = positive_clause.extend_with_literal(literal, schema).unwrap();
+        }
+        let negative_clause =
+            players.extend_with_literal(&ClauseLiteral::Not { conjunction: related }, schema).unwrap();
+
+        let positive_examples = oracle.test_clause(&positive_clause)?;
+        let mut negative_examples: Vec<FoilExample> =
+            oracle.test_clause(&negative_clause)?.into_iter().sorted_by_key(Instance::sort_key).collect();
+        if let Some(max) = config.max_relation_negatives {
+            negative_examples.truncate(max);
+        }
+        let negative_examples = negative_examples.into_iter().collect();
+        Ok(Self::relation_task(oracle, language, target_type, head_types, positive_examples, negative_examples, config))
+    }
+
+    fn relation_task(
+        oracle: O,
+        mut language: HypothesisLanguage,
+        target_type: SchemaType,
+        head_types: Vec<SchemaType>,
+        positive_examples: HashSet<FoilExample>,
+        negative_examples: HashSet<FoilExample>,
+        config: FoilConfig,
+    ) -> Self {
+        language.schema.exclude_relation(&target_type);
+        Self {
+            oracle,
+            class_attribute_label: None,
+            target_type,
+            head_types,
+            language,
+            positive_examples,
+            negative_examples,
+            examples_by_class: HashMap::new(),
+            positive_class: None,
+            config,
+        }
+    }
+
+    // Examples of the class are positive, examples of every other class are negative.
+    pub fn set_positive_class(&mut self, class: &str) {
+        let (positives, negatives): (Vec<_>, Vec<_>) =
+            self.examples_by_class.iter().partition(|(example_class, _)| example_class.as_str() == class);
+        self.positive_examples = positives.into_iter().flat_map(|(_, examples)| examples.iter().cloned()).collect();
+        self.negative_examples = negatives.into_iter().flat_map(|(_, examples)| examples.iter().cloned()).collect();
+        self.positive_class = Some(class.to_owned());
+    }
+
+    // Learns a theory for each class, against the examples of all other classes.
+    pub fn search_one_vs_rest(&mut self) -> Result<BTreeMap<String, Vec<Clause>>, typedb_driver::Error> {
+        let classes: Vec<String> = self.examples_by_class.keys().sorted().cloned().collect();
+        let mut theories = BTreeMap::new();
+        for class in classes {
+            println!("Learning theory for class: {}", class);
+            self.set_positive_class(&class);
+            theories.insert(class, self.search()?);
+        }
+        Ok(theories)
+    }
+
+    pub fn deconstruct(self) -> O {
+        self.oracle
     }
 
     pub(super) fn initial_clause(&self) -> Clause {
-        Clause::new_empty().extend_with_isa(
-            &ClauseVariable(ClauseVariable::INSTANCE_VAR_NAME.to_owned()),
-            &self.target_type,
-            &self.language.schema,
-        )
+        Clause::new_from_head(&self.head_types, &self.language.schema)
     }
 
     // FOIL search algorithm
     pub fn search(&self) -> Result<Vec<Clause>, typedb_driver::Error> {
+        self.search_with(&self.positive_examples, &self.negative_examples, |positives, negatives, theory| {
+            self.learn_clause(positives, negatives, theory)
+        })
+    }
+
+    // The covering loop, learning each clause with learn_clause until all positives are covered.
+    pub(crate) fn search_with(
+        &self,
+        positives: &HashSet<FoilExample>,
+        negatives: &HashSet<FoilExample>,
+        mut learn_clause: impl FnMut(
+            &HashSet<FoilExample>,
+            &HashSet<FoilExample>,
+            &[Clause],
+        ) -> Result<Option<Clause>, typedb_driver::Error>,
+    ) -> Result<Vec<Clause>, typedb_driver::Error> {
+        // Classes other than booleans need set_positive_class first, or all examples would be negative.
+        if self.class_attribute_label.is_some() && self.positive_class.is_none() {
+            return Err(typedb_driver::Error::Other(
+                "No positive class set: call set_positive_class or search_one_vs_rest".to_owned(),
+            ));
+        }
         let mut theory = Vec::new();
-        let mut uncovered_positives = self.positive_examples.clone();
-        let mut all_negatives = self.negative_examples.clone();
+        let mut uncovered_positives = positives.clone();
+        let all_negatives = negatives.clone();
 
         // Learn clauses until all positive examples are covered
         while !uncovered_positives.is_empty() {
             println!("Learning new clause. Uncovered positives: {}", uncovered_positives.len());
 
-            let Some(clause) = self.learn_clause(&uncovered_positives, &all_negatives)? else {
+            let Some(clause) = learn_clause(&uncovered_positives, &all_negatives, &theory)? else {
                 break;
             };
 
-            // Find which positives this clause covers
-            let covered_instances = self.typedb.test_clause(&clause)?;
+            // Find which positives this clause covers. Recursive calls see the theory including the clause.
+            theory.push(clause);
+            let clause = theory.last().unwrap();
+            let covered_instances = self.test_clause(clause, &theory)?;
             println!(
                 "Learnt clause: {}; Covers pos/neg: {}/{} \n---",
                 clause,
@@ -107,11 +385,13 @@ impl FoilLearningTask {
             );
             uncovered_positives.retain(|ex| !covered_instances.contains(ex));
             // uncovered_negatives.retain(|ex| !covered_instances.contains(ex));
-            theory.push(clause);
 
             // Safety check to prevent infinite loops
-            if theory.len() > Self::MAX_THEORY_LENGTH {
-                eprintln!("Warning: Learned {} clauses, stopping to prevent infinite loop", Self::MAX_THEORY_LENGTH);
+            if theory.len() > self.config.max_theory_length {
+                eprintln!(
+                    "Warning: Learned {} clauses, stopping to prevent infinite loop",
+                    self.config.max_theory_length
+                );
                 break;
             }
         }
@@ -120,83 +400,177 @@ impl FoilLearningTask {
         Ok(theory)
     }
 
+    // The theory found by search, merged into a single query with a disjunctive tail
+    pub fn search_merged(&self) -> Result<Option<Clause>, typedb_driver::Error> {
+        Ok(Clause::new_from_disjunction(&self.search()?))
+    }
+
     // Learn a single clause that covers some positive examples without covering negatives
     fn learn_clause(
         &self,
         target_positives: &HashSet<FoilExample>,
         target_negatives: &HashSet<FoilExample>,
+        theory: &[Clause],
     ) -> Result<Option<Clause>, typedb_driver::Error> {
-        let mut clause = self.initial_clause();
-
-        let mut covered_positives = target_positives.clone();
-        let mut covered_negatives = target_negatives.clone();
-
-        while clause.len() < Self::MAX_CLAUSE_LENGTH && !covered_negatives.is_empty() && !covered_positives.is_empty() {
-            // Get instances covered by current clause
-            let covered_instances = self.typedb.test_clause(&clause)?;
-
-            covered_positives.retain(|x| covered_instances.contains(x));
-            covered_negatives.retain(|x| covered_instances.contains(x));
-            // Generate and evaluate refinements
-            let mut refinements = clause.refine(&self.language);
-            if Self::LOOKAHEAD_ONE {
-                refinements.extend(clause.refine_to_length(&self.language, 2))
-            }
-
-            // Find best refinement using FOIL information gain
-            let mut best_clause = None;
-            let mut best_gain = f64::NEG_INFINITY;
-
-            for refinement in refinements {
-                let refined_covered: HashSet<Instance> = self.typedb.test_clause(&refinement)?;
-                let p_new = refined_covered.intersection(&target_positives).count() as f64;
-                let n_new = refined_covered.intersection(&target_negatives).count() as f64;
+        self.learn_clause_from(self.initial_clause(), target_positives, target_negatives, theory, |clause| {
+            self.refinements(clause, theory)
+        })
+    }
 
-                // Skip refinements that cover no positives
-                if p_new == 0.0 {
+    // Beam search: keeps the config.beam_width refinements scoring highest by config.heuristic at each step. Returns the complete
+    // clause (covering no negatives) which covers the most positives, or the best clause of the last beam if none is.
+    pub(crate) fn learn_clause_from(
+        &self,
+        clause: Clause,
+        target_positives: &HashSet<FoilExample>,
+        target_negatives: &HashSet<FoilExample>,
+        theory: &[Clause],
+        refine: impl Fn(&Clause) -> Vec<Clause>,
+    ) -> Result<Option<Clause>, typedb_driver::Error> {
+        let initial = self.beam_entry(clause, 0.0, target_positives, target_negatives, theory)?;
+        if initial.positives.is_empty() {
+            println!("  Clause covers no positives, returning None");
+            return Ok(None);
+        }
+        let total = Coverage { positives: target_positives.len() as f64, negatives: target_negatives.len() as f64 };
+        let mut best_complete: Option<BeamEntry> = None;
+        let mut beam = vec![initial];
+        loop {
+            let mut candidates: Vec<(f64, BeamEntry)> = Vec::new();
+            let mut seen = HashSet::new();
+            for entry in &beam {
+                if self.is_complete(entry) || entry.clause.len() >= self.config.max_clause_length {
                     continue;
                 }
-
-                // FOIL information gain
-                let gain = self.foil_gain(covered_positives.len() as f64, covered_negatives.len() as f64, p_new, n_new);
-
-                if gain > best_gain {
-                    // TODO: Verify that bigger is better
-                    best_gain = gain;
-                    best_clause = Some(refinement);
+                let refinements = refine(&entry.clause);
+                let bits = entry.bits + 1.0 + (refinements.len() as f64).log2();
+                for refinement in refinements {
+                    if !seen.insert(refinement.to_typeql()) {
+                        continue; // Reached from another clause in the beam
+                    }
+                    let refined = self.beam_entry(refinement, bits, target_positives, target_negatives, theory)?;
+                    // Skip refinements that cover no positives, or which the stopping criteria rule out
+                    let allowed = self
+                        .config
+                        .stopping_criteria
+                        .iter()
+                        .all(|criterion| criterion.allows_refinement(refined.coverage(), total, refined.bits));
+                    if refined.positives.is_empty() || !allowed {
+                        continue;
+                    }
+                    let score =
+                        self.config.heuristic.score(entry.coverage(), refined.coverage(), total, refined.clause.len());
+                    candidates.push((score, refined));
                 }
             }
-
-            match best_clause {
-                Some(new_clause) => {
-                    clause = new_clause;
-                }
-                None => {
-                    println!("  No improving refinement found");
-                    break;
+            if candidates.is_empty() {
+                break;
+            }
+            // Stable, so ties keep the order the refinements were generated in.
+            candidates.sort_by(|(score1, _), (score2, _)| score2.total_cmp(score1));
+            candidates.truncate(self.config.beam_width.max(1));
+            beam = candidates.into_iter().map(|(_, entry)| entry).collect();
+            for entry in beam.iter().filter(|entry| self.is_complete(entry)) {
+                if best_complete.as_ref().is_none_or(|best| entry.positives.len() > best.positives.len()) {
+                    best_complete = Some(entry.clone());
                 }
             }
         }
-        if covered_positives.is_empty() {
-            println!("  Clause covers no positives, returning None");
-            Ok(None)
-        } else {
-            if covered_negatives.is_empty() {
-                println!("  Clause is pure (no negatives covered)");
+        let learnt = match best_complete {
+            Some(entry) => {
+                println!("  Clause is complete ({} negatives covered)", entry.negatives.len());
+                entry
+            }
+            None => {
+                println!("  No improving refinement found");
+                beam.swap_remove(0)
             }
-            Ok(Some(clause))
+        };
+        let accepted = self
+            .config
+            .stopping_criteria
+            .iter()
+            .all(|criterion| criterion.allows_clause(learnt.coverage(), total, learnt.bits));
+        if !accepted {
+            println!("  Clause does not meet the stopping criteria, returning None");
+            return Ok(None);
+        }
+        Ok(Some(learnt.clause))
+    }
+
+    // Covers no negatives, or is precise enough for a MinPrecision criterion
+    fn is_complete(&self, entry: &BeamEntry) -> bool {
+        entry.negatives.is_empty()
+            || self.config.stopping_criteria.iter().any(|criterion| criterion.completes(entry.coverage()))
+    }
+
+    fn beam_entry(
+        &self,
+        clause: Clause,
+        bits: f64,
+        target_positives: &HashSet<FoilExample>,
+        target_negatives: &HashSet<FoilExample>,
+        theory: &[Clause],
+    ) -> Result<BeamEntry, typedb_driver::Error> {
+        let covered = self.test_clause(&clause, theory)?;
+        Ok(BeamEntry {
+            positives: target_positives.intersection(&covered).cloned().collect(),
+            negatives: target_negatives.intersection(&covered).cloned().collect(),
+            clause,
+            bits,
+        })
+    }
+
+    fn refinements(&self, clause: &Clause, theory: &[Clause]) -> Vec<Clause> {
+        let mut refinements = clause.refine(&self.language);
+        if self.config.lookahead_one {
+            refinements.extend(clause.refine_to_length(&self.language, 2))
+        }
+        if self.config.recursive && !theory.is_empty() {
+            refinements.extend(self.recursive_refinements(clause));
         }
+        refinements
+    }
+
+    // Calls to the theory on each tuple of variables which may be of the head types, other than the head itself
+    pub fn recursive_refinements(&self, clause: &Clause) -> Vec<Clause> {
+        let schema = &self.language.schema;
+        self.head_types
+            .iter()
+            .map(|type_| clause.unifiable_variables(&schema.subtypes[type_]).cloned().collect::<Vec<_>>())
+            .multi_cartesian_product()
+            .filter(|outputs| outputs.as_slice() != clause.head())
+            .filter_map(|outputs| clause.extend_with_call(&self.function_name(), outputs))
+            .collect()
     }
 
-    // FOIL information gain heuristic
-    fn foil_gain(&self, p_old: f64, n_old: f64, p_new: f64, n_new: f64) -> f64 {
-        if p_new == 0.0 || p_old == 0.0 {
-            return f64::NEG_INFINITY;
+    fn test_clause(&self, clause: &Clause, theory: &[Clause]) -> Result<HashSet<Instance>, typedb_driver::Error> {
+        match self.theory_function(theory) {
+            Some(function) if clause.calls(&self.function_name()) => {
+                self.oracle.test_clause_with_functions(clause, &[function])
+            }
+            _ => self.oracle.test_clause(clause),
         }
-        // TODO: Verify we're not missing a -ve and the best clause has the highest gain
-        let old_score = (p_old / (p_old + n_old)).log2();
-        let new_score = (p_new / (p_new + n_new)).log2();
+    }
+
+    pub fn function_name(&self) -> String {
+        format!("predicted_{}", self.target_type.label())
+    }
+
+    // The theory as a `define` query, so that predictions can be queried with `match let $x in predicted_<target>();`
+    pub fn theory_definition(&self, theory: &[Clause]) -> Option<String> {
+        Some(format!("define\n{}", self.theory_function(theory)?))
+    }
 
-        p_new * (new_score - old_score)
+    // The theory as a TypeQL function returning the instances it covers. Clauses may call the function itself.
+    pub fn theory_function(&self, theory: &[Clause]) -> Option<String> {
+        let body = Clause::new_from_disjunction(theory)?;
+        Some(format!(
+            "fun {}() -> {{ {} }}:\nmatch\n{};\nreturn {{ {} }};",
+            self.function_name(),
+            self.head_types.iter().join(", "),
+            body.to_typeql(),
+            body.head().iter().join(", ")
+        ))
     }
 }
@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+
+use itertools::Itertools;
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::{
+    CoverageOracle, Instance,
+    clause::Clause,
+    foil::{FoilExample, FoilLearningTask},
+};
+
+impl<O: CoverageOracle> FoilLearningTask<O> {
+    // Reduced error pruning: holds out validation_fraction of the positives and of the negatives, learns the theory
+    // from the rest, then prunes it on the held out examples.
+    pub fn search_pruned(&self, validation_fraction: f64, seed: u64) -> Result<Vec<Clause>, typedb_driver::Error> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (grow_positives, validation_positives) = split(&self.positive_examples, validation_fraction, &mut rng);
+        let (grow_negatives, validation_negatives) = split(&self.negative_examples, validation_fraction, &mut rng);
+        let theory = self.search_with(&grow_positives, &grow_negatives, |positives, negatives, theory| {
+            self.learn_clause(positives, negatives, theory)
+        })?;
+        self.prune(theory, &validation_positives, &validation_negatives)
+    }
+
+    // Repeatedly drops the last literal of a clause, or a whole clause, choosing the simplification with the best
+    // accuracy on the validation examples, as long as accuracy does not decrease.
+    // Without validation positives or negatives, any theory dropping or keeping everything looks perfect, so the
+    // theory is returned as it is.
+    pub fn prune(
+        &self,
+        mut theory: Vec<Clause>,
+        validation_positives: &HashSet<FoilExample>,
+        validation_negatives: &HashSet<FoilExample>,
+    ) -> Result<Vec<Clause>, typedb_driver::Error> {
+        if validation_positives.is_empty() || validation_negatives.is_empty() {
+            println!("No validation positives or negatives, not pruning");
+            return Ok(theory);
+        }
+        let accuracy = |theory: &[Clause]| -> Result<f64, typedb_driver::Error> {
+            let covered = self.test_theory(theory)?;
+            let true_positives = validation_positives.intersection(&covered).count();
+            let true_negatives = validation_negatives.difference(&covered).count();
+            Ok((true_positives + true_negatives) as f64
+                / (validation_positives.len() + validation_negatives.len()) as f64)
+        };
+        let min_clause_length = self.initial_clause().len();
+        let mut current = accuracy(&theory)?;
+        loop {
+            let mut best: Option<(f64, Vec<Clause>)> = None;
+            for index in 0..theory.len() {
+                let mut without_clause = theory.clone();
+                without_clause.remove(index);
+                let mut candidates = vec![without_clause];
+                if theory[index].len() > min_clause_length {
+                    let mut shortened = theory.clone();
+                    shortened[index] = theory[index].prefix(theory[index].len() - 1, &self.language.schema);
+                    candidates.push(shortened);
+                }
+                for candidate in candidates {
+                    let candidate_accuracy = accuracy(&candidate)?;
+                    if candidate_accuracy >= current && best.as_ref().is_none_or(|(best, _)| candidate_accuracy > *best)
+                    {
+                        best = Some((candidate_accuracy, candidate));
+                    }
+                }
+            }
+            let Some((pruned_accuracy, pruned)) = best else {
+                break;
+            };
+            current = pruned_accuracy;
+            theory = pruned;
+        }
+        println!("Pruned theory has {} clauses, validation accuracy {:.3}", theory.len(), current);
+        Ok(theory)
+    }
+
+    // Instances covered by any clause of the theory
+    fn test_theory(&self, theory: &[Clause]) -> Result<HashSet<Instance>, typedb_driver::Error> {
+        let mut covered = HashSet::new();
+        for clause in theory {
+            covered.extend(self.test_clause(clause, theory)?);
+        }
+        Ok(covered)
+    }
+}
+
+// Randomly splits off the given fraction of the examples, returning (rest, fraction).
+fn split(
+    examples: &HashSet<FoilExample>,
+    fraction: f64,
+    rng: &mut StdRng,
+) -> (HashSet<FoilExample>, HashSet<FoilExample>) {
+    // Sorted first, so the split only depends on the seed and not on the order of the HashSet.
+    let mut shuffled: Vec<FoilExample> = examples.iter().cloned().sorted_by_key(|example| example.sort_key()).collect();
+    shuffled.shuffle(rng);
+    let held_out = (fraction * shuffled.len() as f64).round() as usize;
+    let rest = shuffled.split_off(held_out);
+    (rest.into_iter().collect(), shuffled.into_iter().collect())
+}
@@ -0,0 +1,52 @@
+use crate::foil::heuristic::Coverage;
+
+// Noise-tolerant stopping criteria. Each is checked when growing a clause, and again before adding it to the theory.
+#[derive(Debug, Clone)]
+pub enum StoppingCriterion {
+    // Quinlan: a clause may not take more bits to encode than the positives it covers, indicated explicitly.
+    EncodingLength,
+    // Clauses at least this precise are complete, even if they cover negatives. Less precise ones are not added.
+    MinPrecision(f64),
+    // CN2: the likelihood ratio statistic of the covered examples, against the distribution of the target examples,
+    // must reach the threshold. E.g. 3.84 for 95% significance, as it is chi-square with one degree of freedom.
+    Significance { threshold: f64 },
+}
+
+impl StoppingCriterion {
+    // Whether a refinement may be kept while growing the clause. Precision only decides when the clause is complete.
+    pub(crate) fn allows_refinement(&self, covered: Coverage, total: Coverage, clause_bits: f64) -> bool {
+        match self {
+            StoppingCriterion::MinPrecision(_) => true,
+            _ => self.allows_clause(covered, total, clause_bits),
+        }
+    }
+
+    pub fn allows_clause(&self, covered: Coverage, total: Coverage, clause_bits: f64) -> bool {
+        match self {
+            StoppingCriterion::EncodingLength => {
+                clause_bits <= explicit_bits(covered.positives, total.positives + total.negatives)
+            }
+            StoppingCriterion::MinPrecision(min_precision) => covered.precision() >= *min_precision,
+            StoppingCriterion::Significance { threshold } => likelihood_ratio(covered, total) >= *threshold,
+        }
+    }
+
+    pub(crate) fn completes(&self, covered: Coverage) -> bool {
+        matches!(self, StoppingCriterion::MinPrecision(min_precision) if covered.precision() >= *min_precision)
+    }
+}
+
+// Bits to indicate which of the examples are the covered positives: log2(examples) + log2(examples choose positives)
+pub fn explicit_bits(positives: f64, examples: f64) -> f64 {
+    let choose = (1..=positives as usize).map(|i| ((examples - positives + i as f64) / i as f64).log2()).sum::<f64>();
+    examples.log2() + choose
+}
+
+// 2 * sum(observed * ln(observed / expected)) over the positives and negatives covered
+pub fn likelihood_ratio(covered: Coverage, total: Coverage) -> f64 {
+    let covered_examples = covered.positives + covered.negatives;
+    let term = |observed: f64, fraction: f64| {
+        if observed == 0.0 { 0.0 } else { observed * (observed / (covered_examples * fraction)).ln() }
+    };
+    2.0 * (term(covered.positives, total.precision()) + term(covered.negatives, 1.0 - total.precision()))
+}
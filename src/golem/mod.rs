@@ -0,0 +1,114 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::{
+    Instance, TypeDBHelper,
+    clause::Clause,
+    progol::{Neighbourhood, ProgolLearningTask},
+};
+
+// Golem: Bottom-up search from the bottom clauses of positive examples. The best LGG of a sample of pairs of
+// positives is generalised with further positives, as long as it covers at most max_negatives negatives.
+pub struct GolemLearningTask<O: Neighbourhood = TypeDBHelper> {
+    pub progol: ProgolLearningTask<O>, // Examples, hypothesis language and the bottom clauses of examples.
+    pub sample_size: usize,            // Pairs, then candidate examples, whose LGGs are compared at each step.
+    pub max_negatives: usize,          // Negatives a clause may cover, for noisy data.
+    pub seed: u64,
+}
+
+struct Candidate {
+    clause: Clause,
+    positives: usize,
+}
+
+impl<O: Neighbourhood> GolemLearningTask<O> {
+    pub fn new(progol: ProgolLearningTask<O>, sample_size: usize, max_negatives: usize, seed: u64) -> Self {
+        Self { progol, sample_size, max_negatives, seed }
+    }
+
+    pub fn deconstruct(self) -> ProgolLearningTask<O> {
+        self.progol
+    }
+
+    pub fn search(&self) -> Result<Vec<Clause>, typedb_driver::Error> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut bottom_clauses = HashMap::new();
+        let foil = &self.progol.foil;
+        foil.search_with(&foil.positive_examples, &foil.negative_examples, |positives, negatives, _| {
+            self.learn_clause(positives, negatives, &mut bottom_clauses, &mut rng)
+        })
+    }
+
+    fn learn_clause(
+        &self,
+        target_positives: &HashSet<Instance>,
+        target_negatives: &HashSet<Instance>,
+        bottom_clauses: &mut HashMap<Instance, Clause>,
+        rng: &mut StdRng,
+    ) -> Result<Option<Clause>, typedb_driver::Error> {
+        // Sorted, so that the same seed samples the same pairs
+        let positives: Vec<Instance> =
+            target_positives.iter().sorted_by_key(|positive| positive.sort_key()).cloned().collect();
+        let mut bottom_clause = |example: &Instance| -> Result<Clause, typedb_driver::Error> {
+            if !bottom_clauses.contains_key(example) {
+                bottom_clauses.insert(example.clone(), self.progol.bottom_clause(example)?);
+            }
+            Ok(bottom_clauses[example].clone())
+        };
+        if positives.len() < 2 {
+            // Nothing to generalise with. The bottom clause covers the example itself, and maybe too many negatives.
+            let Some(example) = positives.first() else { return Ok(None) };
+            let candidate = self.better(None, bottom_clause(example)?, target_positives, target_negatives)?;
+            return Ok(candidate.map(|candidate| candidate.clause));
+        }
+
+        let schema = &self.progol.foil.language.schema;
+        let mut best: Option<Candidate> = None;
+        for _ in 0..self.sample_size {
+            let pair: Vec<&Instance> = positives.choose_multiple(rng, 2).collect();
+            let lgg = bottom_clause(pair[0])?.lgg(&bottom_clause(pair[1])?, schema);
+            best = self.better(best, lgg, target_positives, target_negatives)?;
+        }
+        let Some(mut best) = best else {
+            println!("  No LGG of a pair of positives covers few enough negatives");
+            return Ok(None);
+        };
+
+        loop {
+            let covered = self.progol.foil.oracle.test_clause(&best.clause)?;
+            let uncovered: Vec<&Instance> = positives.iter().filter(|example| !covered.contains(example)).collect();
+            let mut improved = None;
+            for example in uncovered.choose_multiple(rng, self.sample_size) {
+                let lgg = best.clause.lgg(&bottom_clause(example)?, schema);
+                improved = self.better(improved, lgg, target_positives, target_negatives)?;
+            }
+            match improved {
+                Some(candidate) if candidate.positives > best.positives => best = candidate,
+                _ => break,
+            }
+        }
+        println!("  LGG covers {} positives", best.positives);
+        Ok(Some(best.clause))
+    }
+
+    // The candidate covering more positives, among those covering at most max_negatives negatives.
+    fn better(
+        &self,
+        best: Option<Candidate>,
+        clause: Clause,
+        target_positives: &HashSet<Instance>,
+        target_negatives: &HashSet<Instance>,
+    ) -> Result<Option<Candidate>, typedb_driver::Error> {
+        let covered = self.progol.foil.oracle.test_clause(&clause)?;
+        if covered.intersection(target_negatives).count() > self.max_negatives {
+            return Ok(best);
+        }
+        let positives = covered.intersection(target_positives).count();
+        match best {
+            Some(best) if best.positives >= positives => Ok(Some(best)),
+            _ => Ok(Some(Candidate { clause, positives })),
+        }
+    }
+}
@@ -5,20 +5,37 @@ use std::{
     hash::{Hash, Hasher},
 };
 
+use itertools::Itertools;
 use typedb_driver::{
     Promise, Transaction, TypeDBDriver,
-    concept::{Concept, type_::Type},
+    concept::{
+        Concept, ValueType,
+        type_::{AttributeType, EntityType, RelationType, RoleType, Type},
+    },
 };
 
 use crate::TypeDBHelper;
 
 pub enum LanguageDiscoveryOption {
     CategoricalAttributes { type_labels: Vec<String> },
+    // Also propose binding new relation/player variables to existing type-compatible variables.
+    UnifyVariables,
+    // Integer/double attributes compared against at most max_thresholds thresholds discovered from the data.
+    NumericAttributes { type_labels: Vec<String>, max_thresholds: usize },
+    // Attributes which are added as variables and compared against other attribute variables.
+    ComparableAttributes { type_labels: Vec<String> },
+    // Also propose `not { ... }` of refinements with up to max_length literals. Their number grows exponentially with
+    // the length, so it is capped, see HypothesisLanguage::with_max_negated_refinements.
+    Negation { max_length: usize },
 }
 
 #[derive(Debug, Clone)]
 pub struct HypothesisLanguage {
     pub schema: Schema,
+    pub unify_variables: bool,
+    pub comparable_attribute_types: BTreeSet<SchemaType>,
+    pub max_negation_length: usize,
+    pub max_negated_refinements: usize, // Of each length, see Clause::refine
 }
 
 impl HypothesisLanguage {
@@ -26,6 +43,7 @@ impl HypothesisLanguage {
     const RELATES_QUERY: &'static str = "match $left relates $right;";
     const PLAYS_QUERY: &'static str = "match $left plays $right;";
     const SUB_QUERY: &'static str = "match $left sub $right;";
+    const MAX_NEGATED_REFINEMENTS: usize = 1000;
 
     fn _exec(
         tx: &Transaction,
@@ -67,14 +85,58 @@ impl HypothesisLanguage {
             let (plays, players) = _collect_lr(Self::_exec(&tx, Self::PLAYS_QUERY)?)?;
             let (_, subtypes) = _collect_lr(Self::_exec(&tx, Self::SUB_QUERY)?)?;
             let categorical_attribute_values = Self::read_categorical_attribute_values(&tx, options)?;
-            Schema { owns, owners, relates, related_by, plays, players, subtypes, categorical_attribute_values }
+            let numeric_attribute_thresholds = Self::read_numeric_attribute_thresholds(&tx, options)?;
+            Schema {
+                owns,
+                owners,
+                relates,
+                related_by,
+                plays,
+                players,
+                subtypes,
+                categorical_attribute_values,
+                numeric_attribute_thresholds,
+            }
         };
 
-        Ok(Self { schema })
+        Ok(Self::from_schema(schema, options))
+    }
+
+    // The language over a schema whose attribute values and thresholds are already known, e.g. an in-memory one.
+    pub fn from_schema(schema: Schema, options: &[LanguageDiscoveryOption]) -> Self {
+        let unify_variables = options.iter().any(|option| matches!(option, LanguageDiscoveryOption::UnifyVariables));
+        let comparable_attribute_types = options
+            .iter()
+            .filter_map(|option| match option {
+                LanguageDiscoveryOption::ComparableAttributes { type_labels } => Some(type_labels),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|label| schema.lookup_type(label).cloned())
+            .collect();
+        let max_negation_length = options
+            .iter()
+            .filter_map(|option| match option {
+                LanguageDiscoveryOption::Negation { max_length } => Some(*max_length),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        Self {
+            schema,
+            unify_variables,
+            comparable_attribute_types,
+            max_negation_length,
+            max_negated_refinements: Self::MAX_NEGATED_REFINEMENTS,
+        }
+    }
+
+    pub fn with_max_negated_refinements(self, max_negated_refinements: usize) -> Self {
+        Self { max_negated_refinements, ..self }
     }
 
     pub(crate) fn lookup_type(&self, label: &str) -> Option<SchemaType> {
-        self.schema.subtypes.keys().find(|t| t.label() == label).cloned()
+        self.schema.lookup_type(label).cloned()
     }
 
     fn read_categorical_attribute_values(
@@ -84,11 +146,9 @@ impl HypothesisLanguage {
         let mut categorical_attribute_values = HashMap::new();
         options
             .iter()
-            .filter_map(|option| {
-                match option {
-                    LanguageDiscoveryOption::CategoricalAttributes { type_labels } => Some(type_labels),
-                    // _ => None,
-                }
+            .filter_map(|option| match option {
+                LanguageDiscoveryOption::CategoricalAttributes { type_labels } => Some(type_labels),
+                _ => None,
             })
             .flat_map(|labels| labels.iter())
             .try_for_each(|label| {
@@ -104,9 +164,82 @@ impl HypothesisLanguage {
             })?;
         Ok(categorical_attribute_values)
     }
+
+    fn read_numeric_attribute_thresholds(
+        tx: &Transaction,
+        options: &[LanguageDiscoveryOption],
+    ) -> Result<HashMap<SchemaType, Vec<typedb_driver::concept::value::Value>>, typedb_driver::Error> {
+        let mut numeric_attribute_thresholds = HashMap::new();
+        options
+            .iter()
+            .filter_map(|option| match option {
+                LanguageDiscoveryOption::NumericAttributes { type_labels, max_thresholds } => {
+                    Some(type_labels.iter().map(move |label| (label, *max_thresholds)))
+                }
+                _ => None,
+            })
+            .flatten()
+            .try_for_each(|(label, max_thresholds)| {
+                // One row per ownership, so the thresholds follow the distribution of owned values.
+                let mut observed: HashMap<SchemaType, Vec<typedb_driver::concept::value::Value>> = HashMap::new();
+                Self::_exec(
+                    tx,
+                    format!("match attribute $left label {}; $right isa $left; $owner has $right;", label).as_str(),
+                )?
+                .try_for_each(|result| {
+                    let (type_, attr) = result?;
+                    observed.entry(type_.into()).or_default().push(attr.try_get_value().unwrap().clone());
+                    Ok::<_, typedb_driver::Error>(())
+                })?;
+                observed.into_iter().for_each(|(type_, values)| {
+                    numeric_attribute_thresholds.insert(type_, numeric_thresholds(&values, max_thresholds));
+                });
+                Ok::<_, typedb_driver::Error>(())
+            })?;
+        Ok(numeric_attribute_thresholds)
+    }
 }
 
-#[derive(Debug, Clone)]
+// Midpoints between consecutive distinct values. If there are too many, pick those at evenly spaced quantiles.
+// Integer midpoints are rounded down, so `<= t` and `> t` partition the observed values exactly.
+// Values which are not numbers are ignored.
+pub fn numeric_thresholds(
+    values: &[typedb_driver::concept::value::Value],
+    max_thresholds: usize,
+) -> Vec<typedb_driver::concept::value::Value> {
+    use typedb_driver::concept::value::Value;
+    let is_integer = values.iter().all(|value| !matches!(value, Value::Double(_)));
+    let mut sorted: Vec<f64> = values
+        .iter()
+        .filter_map(|value| match value {
+            Value::Integer(integer) => Some(*integer as f64),
+            Value::Double(double) => Some(*double),
+            _ => None,
+        })
+        .collect();
+    sorted.sort_by(f64::total_cmp);
+    let distinct: Vec<f64> = sorted.iter().cloned().dedup().collect();
+    let midpoint = |i: usize| -> Value {
+        let mid = (distinct[i] + distinct[i + 1]) / 2.0;
+        if is_integer { Value::Integer(mid.floor() as i64) } else { Value::Double(mid) }
+    };
+
+    let boundaries = distinct.len().saturating_sub(1);
+    if boundaries <= max_thresholds {
+        return (0..boundaries).map(midpoint).collect();
+    }
+    (1..=max_thresholds)
+        .map(|k| {
+            let quantile_value = sorted[k * sorted.len() / (max_thresholds + 1)];
+            let position = distinct.partition_point(|v| *v < quantile_value);
+            position.max(1) - 1
+        })
+        .dedup()
+        .map(midpoint)
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Schema {
     // Entity/Relation types that own attribute types
     pub owns: HashMap<SchemaType, BTreeSet<SchemaType>>,
@@ -122,15 +255,101 @@ pub struct Schema {
 
     pub subtypes: HashMap<SchemaType, BTreeSet<SchemaType>>,
     pub categorical_attribute_values: HashMap<SchemaType, Vec<typedb_driver::concept::value::Value>>,
+    pub numeric_attribute_thresholds: HashMap<SchemaType, Vec<typedb_driver::concept::value::Value>>,
+}
+
+impl Schema {
+    // Every type is its own subtype, as in `match $left sub $right;`
+    pub fn define_type(&mut self, type_: SchemaType) {
+        self.subtypes.entry(type_.clone()).or_default().insert(type_);
+    }
+
+    // The subtype, and its own subtypes, become subtypes of the supertype and of all its supertypes.
+    pub fn define_sub(&mut self, subtype: &SchemaType, supertype: &SchemaType) {
+        self.define_type(subtype.clone());
+        self.define_type(supertype.clone());
+        let added = self.subtypes[subtype].clone();
+        self.subtypes
+            .values_mut()
+            .filter(|subtypes| subtypes.contains(supertype))
+            .for_each(|subtypes| subtypes.extend(added.iter().cloned()));
+    }
+
+    pub fn define_owns(&mut self, owner: &SchemaType, attribute: &SchemaType) {
+        self.owns.entry(owner.clone()).or_default().insert(attribute.clone());
+        self.owners.entry(attribute.clone()).or_default().insert(owner.clone());
+    }
+
+    pub fn define_relates(&mut self, relation: &SchemaType, role: &SchemaType) {
+        self.define_type(role.clone());
+        self.relates.entry(relation.clone()).or_default().insert(role.clone());
+        self.related_by.entry(role.clone()).or_default().insert(relation.clone());
+    }
+
+    pub fn define_plays(&mut self, player: &SchemaType, role: &SchemaType) {
+        self.plays.entry(player.clone()).or_default().insert(role.clone());
+        self.players.entry(role.clone()).or_default().insert(player.clone());
+    }
+
+    pub fn lookup_type(&self, label: &str) -> Option<&SchemaType> {
+        self.subtypes.keys().find(|t| t.label() == label)
+    }
+
+    // Removes a relation type, and its roles, from the hypothesis language. E.g., the relation being learnt.
+    pub fn exclude_relation(&mut self, relation: &SchemaType) {
+        let roles = self.relates.remove(relation).unwrap_or_default();
+        for role in &roles {
+            self.related_by.remove(role);
+            self.players.remove(role);
+        }
+        self.plays.values_mut().for_each(|played| played.retain(|role| !roles.contains(role)));
+        for attribute in self.owns.remove(relation).unwrap_or_default() {
+            if let Some(owners) = self.owners.get_mut(&attribute) {
+                owners.remove(relation);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct SchemaType(typedb_driver::concept::type_::Type);
 
 impl SchemaType {
+    pub fn entity(label: &str) -> Self {
+        Self(Type::EntityType(EntityType { label: label.to_owned() }))
+    }
+
+    pub fn relation(label: &str) -> Self {
+        Self(Type::RelationType(RelationType { label: label.to_owned() }))
+    }
+
+    // Roles are scoped by their relation, e.g. `employment:employee`
+    pub fn role(label: &str) -> Self {
+        Self(Type::RoleType(RoleType { label: label.to_owned() }))
+    }
+
+    pub fn attribute(label: &str, value_type: Option<ValueType>) -> Self {
+        Self(Type::AttributeType(AttributeType { label: label.to_owned(), value_type }))
+    }
+
+    pub fn is_entity(&self) -> bool {
+        matches!(self.0, Type::EntityType(_))
+    }
+
+    pub fn is_relation(&self) -> bool {
+        matches!(self.0, Type::RelationType(_))
+    }
+
     pub fn label(&self) -> &str {
         self.0.label()
     }
+
+    pub fn value_type(&self) -> Option<&ValueType> {
+        match &self.0 {
+            Type::AttributeType(type_) => type_.value_type(),
+            _ => None,
+        }
+    }
 }
 
 impl Hash for SchemaType {
@@ -1,23 +1,63 @@
 use std::collections::HashSet;
 
-use typedb_driver::{Promise, Transaction, TransactionType, TypeDBDriver, answer::ConceptRow, concept::Concept};
+use itertools::Itertools;
+use typedb_driver::{
+    Promise, Transaction, TransactionType, TypeDBDriver,
+    answer::ConceptRow,
+    concept::{Concept, Value},
+};
 
-use crate::clause::{Clause, ClauseVariable};
+use crate::clause::Clause;
 
 pub mod clause;
 pub mod language;
+pub mod memory;
+pub mod prolog;
 
+pub mod anomaly;
 pub mod foil;
+pub mod golem;
+pub mod progol;
 pub mod tilde;
+pub mod warmr;
 
 const INDENT: &'static str = "  ";
 
+// The IIDs bound to the head variables of a clause. A single IID, unless the target is a tuple.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct Instance(typedb_driver::IID);
+pub struct Instance(Vec<typedb_driver::IID>);
+
+impl Instance {
+    pub fn new(iids: Vec<typedb_driver::IID>) -> Self {
+        Self(iids)
+    }
+
+    pub fn from_concepts<'a>(concepts: impl IntoIterator<Item = &'a Concept>) -> Self {
+        Self(
+            concepts
+                .into_iter()
+                .map(|concept| concept.try_get_iid().expect("Expected instance variant which has IID").clone())
+                .collect(),
+        )
+    }
+
+    // Orders instances where any one would do, e.g. to choose a seed, so that results do not depend on hashing.
+    pub(crate) fn sort_key(&self) -> Vec<String> {
+        self.0.iter().map(|iid| iid.to_string()).collect()
+    }
+}
 
 impl From<&Concept> for Instance {
     fn from(value: &Concept) -> Self {
-        Self(value.try_get_iid().expect("Expected instance variant which has IID").clone())
+        Self::from_concepts([value])
+    }
+}
+
+// Label of the class an example belongs to. Strings are unquoted, other values as they are written in TypeQL.
+pub(crate) fn class_label(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        _ => value.to_string(),
     }
 }
 
@@ -26,29 +66,60 @@ pub struct TypeDBHelper {
     pub database: String,
 }
 
+// Which instances of the head variables satisfy a clause. Backed by TypeDB, or by memory::FactStore.
+pub trait CoverageOracle {
+    // Returns example instances which satisfy the clause
+    fn test_clause(&self, clause: &Clause) -> Result<HashSet<Instance>, typedb_driver::Error> {
+        self.test_clause_with_functions(clause, &[])
+    }
+
+    // Like test_clause, but first defines the functions the clause calls.
+    fn test_clause_with_functions(
+        &self,
+        clause: &Clause,
+        functions: &[String],
+    ) -> Result<HashSet<Instance>, typedb_driver::Error>;
+}
+
 impl TypeDBHelper {
     pub fn new(driver: TypeDBDriver, database: String) -> Self {
         Self { driver, database }
     }
 
-    // Returns example instances which satisfy the clause
-    pub fn test_clause(&self, clause: &Clause) -> Result<HashSet<Instance>, typedb_driver::Error> {
-        // println!("TESTING: {clause}");
-        let query = format!("match {}; select ${};", clause.to_typeql(), ClauseVariable::INSTANCE_VAR_NAME);
-        let tx = self.driver.transaction(self.database.as_str(), TransactionType::Read)?;
-        tx.query(query)
-            .resolve()?
-            .into_rows()
-            .map(|row| Ok(row?.get(ClauseVariable::INSTANCE_VAR_NAME).unwrap().unwrap().into()))
-            .collect()
-    }
-
     pub(crate) fn read_tx(&self) -> Result<Transaction, typedb_driver::Error> {
         self.driver.transaction(self.database.as_str(), TransactionType::Read)
     }
+
+    // Runs a `define` query in a schema transaction, and commits it.
+    pub fn define(&self, query: &str) -> Result<(), typedb_driver::Error> {
+        let tx = self.driver.transaction(self.database.as_str(), TransactionType::Schema)?;
+        tx.query(query).resolve()?;
+        tx.commit().resolve()
+    }
     //
     // pub(crate) fn query(&self, query: &str) -> Result<impl Iterator<Item=Result<ConceptRow, typedb_driver::Error>>, typedb_driver::Error> {
     //     let tx = self.driver.transaction(self.database.as_str(), TransactionType::Read)?;
     //     Ok(tx.query(query))
     // }
 }
+
+impl CoverageOracle for TypeDBHelper {
+    fn test_clause_with_functions(
+        &self,
+        clause: &Clause,
+        functions: &[String],
+    ) -> Result<HashSet<Instance>, typedb_driver::Error> {
+        // println!("TESTING: {clause}");
+        let preamble: String = functions.iter().map(|function| format!("with {function}\n")).collect();
+        let query = format!("{}match {}; select {};", preamble, clause.to_typeql(), clause.head().iter().join(", "));
+        let tx = self.driver.transaction(self.database.as_str(), TransactionType::Read)?;
+        tx.query(query)
+            .resolve()?
+            .into_rows()
+            .map(|row| {
+                let row = row?;
+                Ok(Instance::from_concepts(clause.head().iter().map(|var| row.get(var.name()).unwrap().unwrap())))
+            })
+            .collect()
+    }
+}
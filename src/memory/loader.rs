@@ -0,0 +1,299 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use typedb_driver::{
+    IID,
+    concept::{Value, ValueType},
+};
+use typeql::{
+    Definable, Expression, Label, Pattern, Statement, TypeRef, TypeRefAny, Variable,
+    query::{QueryStructure, SchemaQuery, stage::Stage},
+    schema::definable::type_::CapabilityBase,
+    statement::thing::{Constraint, Has, HasValue, Head, Relation, RolePlayer, Thing},
+    token,
+    type_::NamedType,
+    value::ValueLiteral,
+};
+
+use crate::{
+    language::{Schema, SchemaType},
+    memory::FactStore,
+};
+
+type Result<T> = std::result::Result<T, typedb_driver::Error>;
+
+impl Schema {
+    // Reads TypeQL `define` queries. As when read from TypeDB, types also own, relate and play what their supertypes do.
+    pub fn from_typeql(typeql: &str) -> Result<Self> {
+        let mut declarations = Vec::new();
+        for query in typeql::parse_queries(typeql).map_err(error)? {
+            let QueryStructure::Schema(SchemaQuery::Define(define)) = query.into_structure() else {
+                return Err(error("Expected only define queries in the schema"));
+            };
+            // Functions and structs are not part of the hypothesis language
+            declarations.extend(define.definables.into_iter().filter_map(|definable| match definable {
+                Definable::TypeDeclaration(type_) => Some(type_),
+                _ => None,
+            }));
+        }
+
+        // The kind and value type of a type may be declared once, or inherited from its supertype
+        let mut kinds = HashMap::new();
+        let mut value_types = HashMap::new();
+        let mut supertypes = HashMap::new();
+        for declaration in &declarations {
+            let label = label_of(&declaration.label);
+            if let Some(kind) = declaration.kind {
+                kinds.insert(label.clone(), kind);
+            }
+            for capability in &declaration.capabilities {
+                match &capability.base {
+                    CapabilityBase::Sub(sub) => {
+                        supertypes.insert(label.clone(), label_of(&sub.supertype_label));
+                    }
+                    CapabilityBase::ValueType(value_type) => {
+                        value_types.insert(label.clone(), value_type_of(&value_type.value_type));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let mut schema = Schema::default();
+        let mut types = HashMap::new();
+        for declaration in &declarations {
+            let label = label_of(&declaration.label);
+            let type_ = match inherited(&label, &kinds, &supertypes) {
+                Some(token::Kind::Entity) => SchemaType::entity(&label),
+                Some(token::Kind::Relation) => SchemaType::relation(&label),
+                Some(token::Kind::Attribute) => {
+                    SchemaType::attribute(&label, inherited(&label, &value_types, &supertypes).cloned())
+                }
+                _ => return Err(error(format!("Expected {label} to be an entity, relation or attribute type"))),
+            };
+            schema.define_type(type_.clone());
+            types.insert(label, type_);
+        }
+        let lookup = |label: &str| types.get(label).ok_or_else(|| error(format!("Type {label} is not defined")));
+
+        for declaration in &declarations {
+            let type_ = lookup(&label_of(&declaration.label))?;
+            for capability in &declaration.capabilities {
+                match &capability.base {
+                    CapabilityBase::Sub(sub) => schema.define_sub(type_, lookup(&label_of(&sub.supertype_label))?),
+                    CapabilityBase::Owns(owns) => schema.define_owns(type_, lookup(&type_ref_any_label(&owns.owned)?)?),
+                    CapabilityBase::Relates(relates) => {
+                        let role = format!("{}:{}", type_.label(), type_ref_any_label(&relates.related)?);
+                        schema.define_relates(type_, &SchemaType::role(&role));
+                    }
+                    CapabilityBase::Plays(plays) => {
+                        let role = format!("{}:{}", label_of(&plays.role.scope), label_of(&plays.role.name));
+                        schema.define_plays(type_, &SchemaType::role(&role));
+                    }
+                    CapabilityBase::Alias(_) | CapabilityBase::ValueType(_) => {}
+                }
+            }
+        }
+
+        for (supertype, subtypes) in schema.subtypes.clone() {
+            for subtype in subtypes.iter().filter(|subtype| **subtype != supertype) {
+                for attribute in schema.owns.get(&supertype).cloned().unwrap_or_default() {
+                    schema.define_owns(subtype, &attribute);
+                }
+                for role in schema.relates.get(&supertype).cloned().unwrap_or_default() {
+                    schema.define_relates(subtype, &role);
+                }
+                for role in schema.plays.get(&supertype).cloned().unwrap_or_default() {
+                    schema.define_plays(subtype, &role);
+                }
+            }
+        }
+        Ok(schema)
+    }
+}
+
+impl FactStore {
+    // A store of the data inserted by TypeQL `insert` queries, into the schema defined by TypeQL `define` queries.
+    pub fn from_typeql(schema: &str, data: &str) -> Result<Self> {
+        let mut store = Self::new(Schema::from_typeql(schema)?);
+        store.insert_typeql(data)?;
+        Ok(store)
+    }
+
+    // E.g. examples/bongard/schema.tql and data.tql
+    pub fn from_typeql_files(schema: &Path, data: &Path) -> Result<Self> {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path).map_err(|err| error(format!("Could not read {}: {err}", path.display())))
+        };
+        Self::from_typeql(&read(schema)?, &read(data)?)
+    }
+
+    // Inserts the data of TypeQL `insert` queries. Attributes are inserted with `has <type> <value>`, not as variables.
+    pub fn insert_typeql(&mut self, typeql: &str) -> Result<()> {
+        for query in typeql::parse_queries(typeql).map_err(error)? {
+            let QueryStructure::Pipeline(pipeline) = query.into_structure() else {
+                return Err(error("Expected only insert queries in the data"));
+            };
+            let mut patterns = Vec::new();
+            for stage in pipeline.stages {
+                let Stage::Insert(insert) = stage else {
+                    return Err(error("Expected only insert stages in the data"));
+                };
+                patterns.extend(insert.patterns);
+            }
+            let things = patterns
+                .iter()
+                .map(|pattern| match pattern {
+                    Pattern::Statement(Statement::Thing(thing)) => Ok(thing),
+                    _ => Err(error(format!("Expected only statements about instances, found {pattern}"))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.insert_things(&things)?;
+        }
+        Ok(())
+    }
+
+    // Statements with an `isa` are inserted first, so variables may be used before the statement inserting them.
+    fn insert_things(&mut self, things: &[&Thing]) -> Result<()> {
+        let mut vars: HashMap<String, IID> = HashMap::new();
+        let mut inserted = Vec::new();
+        for thing in things {
+            let isa = thing.constraints.iter().find_map(|constraint| match constraint {
+                Constraint::Isa(isa) => Some(&isa.type_),
+                _ => None,
+            });
+            let type_label = match (&thing.head, isa) {
+                (_, Some(type_)) | (Head::Relation(Some(type_), _), None) => type_ref_label(type_)?,
+                _ => {
+                    inserted.push(None);
+                    continue;
+                }
+            };
+            let iid = self.insert(&type_label)?;
+            if let Head::Variable(var) = &thing.head
+                && let Some(name) = var.name()
+            {
+                vars.insert(name.to_owned(), iid.clone());
+            }
+            inserted.push(Some(iid));
+        }
+
+        for (thing, iid) in things.iter().zip(inserted) {
+            let iid = match (iid, &thing.head) {
+                (Some(iid), _) => iid,
+                (None, Head::Variable(var)) => lookup_var(&vars, var)?,
+                (None, Head::Relation(..)) => return Err(error(format!("Expected the type of {thing}"))),
+            };
+            if let Head::Relation(_, relation) = &thing.head {
+                self.insert_role_players(&iid, relation, &vars)?;
+            }
+            for constraint in &thing.constraints {
+                match constraint {
+                    Constraint::Isa(_) => {}
+                    Constraint::Has(has) => self.insert_has(&iid, has)?,
+                    Constraint::Links(links) => self.insert_role_players(&iid, &links.relation, &vars)?,
+                    Constraint::Iid(_) => return Err(error(format!("IIDs cannot be inserted: {thing}"))),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_role_players(&mut self, iid: &IID, relation: &Relation, vars: &HashMap<String, IID>) -> Result<()> {
+        for role_player in &relation.role_players {
+            let RolePlayer::Typed(role, player) = role_player else {
+                return Err(error(format!("Expected a role for {role_player}")));
+            };
+            let role_label = type_ref_any_label(role)?;
+            let player = lookup_var(vars, player)?;
+            self.insert_link(iid, &role_label, &player)?;
+        }
+        Ok(())
+    }
+
+    fn insert_has(&mut self, owner: &IID, has: &Has) -> Result<()> {
+        let (Some(type_), HasValue::Expression(Expression::Value(literal))) = (&has.type_, &has.value) else {
+            return Err(error(format!("Expected an attribute type and value: {has}")));
+        };
+        let label = type_ref_any_label(type_)?;
+        let value_type = self
+            .schema
+            .lookup_type(&label)
+            .and_then(|type_| type_.value_type())
+            .ok_or_else(|| error(format!("Expected {label} to be a defined attribute type")))?;
+        let value = value(&literal.inner, value_type)?;
+        self.insert_attribute(owner, &label, value)
+    }
+}
+
+fn error(message: impl Display) -> typedb_driver::Error {
+    typedb_driver::Error::Other(message.to_string())
+}
+
+fn label_of(label: &Label) -> String {
+    label.ident.as_str_unchecked().to_owned()
+}
+
+fn type_ref_label(type_ref: &TypeRef) -> Result<String> {
+    match type_ref {
+        TypeRef::Label(type_label) => Ok(label_of(type_label)),
+        TypeRef::Scoped(scoped) => Ok(format!("{}:{}", label_of(&scoped.scope), label_of(&scoped.name))),
+        TypeRef::Variable(var) => Err(error(format!("Expected a type label, found {var}"))),
+    }
+}
+
+fn type_ref_any_label(type_ref: &TypeRefAny) -> Result<String> {
+    match type_ref {
+        TypeRefAny::Type(type_ref) => type_ref_label(type_ref),
+        TypeRefAny::List(_) => Err(error(format!("Lists are not supported: {type_ref}"))),
+    }
+}
+
+fn lookup_var(vars: &HashMap<String, IID>, var: &Variable) -> Result<IID> {
+    var.name().and_then(|name| vars.get(name)).cloned().ok_or_else(|| error(format!("{var} is never inserted")))
+}
+
+// The declaration of the type, or of its closest supertype with one
+fn inherited<'a, T>(
+    label: &str,
+    declared: &'a HashMap<String, T>,
+    supertypes: &HashMap<String, String>,
+) -> Option<&'a T> {
+    let mut label = label;
+    // At most one step per type, in case of cycles
+    for _ in 0..=supertypes.len() {
+        if let Some(declaration) = declared.get(label) {
+            return Some(declaration);
+        }
+        label = supertypes.get(label)?;
+    }
+    None
+}
+
+fn value_type_of(named: &NamedType) -> ValueType {
+    match named {
+        NamedType::Label(struct_label) => ValueType::Struct(label_of(struct_label)),
+        NamedType::BuiltinValueType(builtin) => match builtin.token {
+            token::ValueType::Boolean => ValueType::Boolean,
+            token::ValueType::Date => ValueType::Date,
+            token::ValueType::DateTime => ValueType::Datetime,
+            token::ValueType::DateTimeTZ => ValueType::DatetimeTZ,
+            token::ValueType::Decimal => ValueType::Decimal,
+            token::ValueType::Double => ValueType::Double,
+            token::ValueType::Duration => ValueType::Duration,
+            token::ValueType::Integer => ValueType::Integer,
+            token::ValueType::String => ValueType::String,
+        },
+    }
+}
+
+// Only the value types the hypothesis language compares: booleans, numbers and strings.
+fn value(literal: &ValueLiteral, value_type: &ValueType) -> Result<Value> {
+    let text = literal.to_string();
+    let value = match (value_type, literal) {
+        (ValueType::Boolean, ValueLiteral::Boolean(_)) => text.parse().ok().map(Value::Boolean),
+        (ValueType::Integer, ValueLiteral::Integer(_)) => text.parse().ok().map(Value::Integer),
+        (ValueType::Double, ValueLiteral::Integer(_) | ValueLiteral::Double(_)) => text.parse().ok().map(Value::Double),
+        (ValueType::String, ValueLiteral::String(string)) => string.unescape().ok().map(Value::String),
+        _ => None,
+    };
+    value.ok_or_else(|| error(format!("Cannot read {text} as a {value_type} value")))
+}
@@ -0,0 +1,471 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use itertools::Itertools;
+use typedb_driver::{IID, concept::Value};
+
+use crate::{
+    CoverageOracle, Instance, class_label,
+    clause::{Clause, ClauseLiteral, ClauseVariable, ValueComparator},
+    language::{HypothesisLanguage, LanguageDiscoveryOption, Schema, SchemaType, numeric_thresholds},
+    progol::Neighbourhood,
+};
+
+mod loader;
+
+// An in-process store of entities, relations and attributes. Clauses are evaluated by joining their literals,
+// so learners can run on small datasets without a TypeDB server. Functions, and so recursive clauses, are unsupported.
+// Stores can also be loaded from TypeQL `define` and `insert` queries, see FactStore::from_typeql.
+pub struct FactStore {
+    schema: Schema,
+    instances: Vec<(IID, SchemaType)>, // Entities and relations, in insertion order
+    types: HashMap<IID, SchemaType>,
+    ownerships: HashMap<IID, Vec<(SchemaType, Value)>>,
+    links_by_relation: HashMap<IID, Vec<(SchemaType, IID)>>, // role and player
+    links_by_player: HashMap<IID, Vec<(SchemaType, IID)>>,   // role and relation
+}
+
+// What a variable is bound to. Attributes are identified by their type and value, as in TypeDB.
+#[derive(Debug, Clone, PartialEq)]
+enum Binding {
+    Instance(IID),
+    Attribute(SchemaType, Value),
+}
+
+type Row = HashMap<ClauseVariable, Binding>;
+
+impl FactStore {
+    pub fn new(schema: Schema) -> Self {
+        Self {
+            schema,
+            instances: Vec::new(),
+            types: HashMap::new(),
+            ownerships: HashMap::new(),
+            links_by_relation: HashMap::new(),
+            links_by_player: HashMap::new(),
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    // Inserts an entity or relation of the type
+    pub fn insert(&mut self, type_label: &str) -> Result<IID, typedb_driver::Error> {
+        let type_ = match self.schema.lookup_type(type_label) {
+            Some(type_) if type_.is_entity() || type_.is_relation() => type_.clone(),
+            _ => return Err(Self::error(format!("Expected {type_label} to be a defined entity or relation type"))),
+        };
+        let iid = IID::from((self.instances.len() as u64 + 1).to_be_bytes().to_vec());
+        self.types.insert(iid.clone(), type_.clone());
+        self.instances.push((iid.clone(), type_));
+        Ok(iid)
+    }
+
+    pub fn insert_attribute(
+        &mut self,
+        owner: &IID,
+        attribute_label: &str,
+        value: Value,
+    ) -> Result<(), typedb_driver::Error> {
+        let type_ = match self.schema.lookup_type(attribute_label) {
+            Some(type_) if type_.value_type().is_some() => type_.clone(),
+            _ => return Err(Self::error(format!("Expected {attribute_label} to be a defined attribute type"))),
+        };
+        self.ownerships.entry(owner.clone()).or_default().push((type_, value));
+        Ok(())
+    }
+
+    // The role may be unscoped, e.g. `employee` for `employment:employee`, or inherited from a super-relation.
+    pub fn insert_link(&mut self, relation: &IID, role_label: &str, player: &IID) -> Result<(), typedb_driver::Error> {
+        let relation_type =
+            self.types.get(relation).ok_or_else(|| Self::error(format!("Expected {relation} to be inserted")))?;
+        let role = self
+            .role_of(relation_type, role_label)
+            .ok_or_else(|| Self::error(format!("Expected {relation_type} to relate {role_label}")))?;
+        self.links_by_relation.entry(relation.clone()).or_default().push((role.clone(), player.clone()));
+        self.links_by_player.entry(player.clone()).or_default().push((role, relation.clone()));
+        Ok(())
+    }
+
+    pub fn instances_of(&self, type_label: &str) -> Vec<IID> {
+        let Some(type_) = self.schema.lookup_type(type_label) else {
+            return Vec::new();
+        };
+        self.instances.iter().filter(|(_, t)| self.is_a(t, type_)).map(|(iid, _)| iid.clone()).collect()
+    }
+
+    // Instances of the target type by the label of their class (see crate::class_label), as read by discover.
+    pub fn examples_by_class(
+        &self,
+        target_type_label: &str,
+        class_attribute_label: &str,
+    ) -> HashMap<String, HashSet<Instance>> {
+        let mut examples_by_class: HashMap<String, HashSet<Instance>> = HashMap::new();
+        for iid in self.instances_of(target_type_label) {
+            for (_, value) in self.attributes_of(&iid).filter(|(type_, _)| type_.label() == class_attribute_label) {
+                examples_by_class.entry(class_label(value)).or_default().insert(Instance::new(vec![iid.clone()]));
+            }
+        }
+        examples_by_class
+    }
+
+    // Like HypothesisLanguage::fetch_from_typedb, reading attribute values from the store.
+    pub fn language(&self, options: &[LanguageDiscoveryOption]) -> HypothesisLanguage {
+        let mut schema = self.schema.clone();
+        for option in options {
+            match option {
+                LanguageDiscoveryOption::CategoricalAttributes { type_labels } => {
+                    for type_ in type_labels.iter().filter_map(|label| self.schema.lookup_type(label)) {
+                        let mut values: Vec<Value> = Vec::new();
+                        for value in self.values_of(type_) {
+                            if !values.contains(value) {
+                                values.push(value.clone());
+                            }
+                        }
+                        schema.categorical_attribute_values.insert(type_.clone(), values);
+                    }
+                }
+                LanguageDiscoveryOption::NumericAttributes { type_labels, max_thresholds } => {
+                    for type_ in type_labels.iter().filter_map(|label| self.schema.lookup_type(label)) {
+                        // One value per ownership, so the thresholds follow the distribution of owned values.
+                        let values: Vec<Value> = self.values_of(type_).cloned().collect();
+                        schema
+                            .numeric_attribute_thresholds
+                            .insert(type_.clone(), numeric_thresholds(&values, *max_thresholds));
+                    }
+                }
+                _ => {}
+            }
+        }
+        HypothesisLanguage::from_schema(schema, options)
+    }
+
+    fn role_of(&self, relation_type: &SchemaType, role_label: &str) -> Option<SchemaType> {
+        self.schema
+            .relates
+            .iter()
+            .filter(|(relation_supertype, _)| self.is_a(relation_type, relation_supertype))
+            .flat_map(|(_, roles)| roles)
+            .find(|role| role.label() == role_label || role.label().rsplit_once(":").unwrap().1 == role_label)
+            .cloned()
+    }
+
+    fn attributes_of(&self, owner: &IID) -> impl Iterator<Item = &(SchemaType, Value)> {
+        self.ownerships.get(owner).into_iter().flatten()
+    }
+
+    fn values_of<'a>(&'a self, type_: &'a SchemaType) -> impl Iterator<Item = &'a Value> + 'a {
+        self.ownerships.values().flatten().filter(move |(t, _)| t == type_).map(|(_, value)| value)
+    }
+
+    fn is_a(&self, type_: &SchemaType, supertype: &SchemaType) -> bool {
+        type_ == supertype || self.schema.subtypes.get(supertype).is_some_and(|subtypes| subtypes.contains(type_))
+    }
+
+    // Literals which bind variables go first, then those which only test them. Among the former, those which can
+    // look up a variable bound so far go before those which would scan the store.
+    fn ordered<'a>(literals: &'a [ClauseLiteral], mut bound: HashSet<&'a ClauseVariable>) -> Vec<&'a ClauseLiteral> {
+        let phase = |literal: &ClauseLiteral| match literal {
+            ClauseLiteral::Or { .. } => 1,
+            ClauseLiteral::CompareVariables { .. } | ClauseLiteral::CompareConstant { .. } => 2,
+            ClauseLiteral::Not { .. } => 3,
+            _ => 0,
+        };
+        let mut remaining: Vec<&ClauseLiteral> = literals.iter().sorted_by_key(|literal| phase(literal)).collect();
+        let mut ordered = Vec::new();
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .position(|literal| match literal {
+                    ClauseLiteral::Isa { instance, .. } => bound.contains(instance),
+                    ClauseLiteral::Has { owner, .. } | ClauseLiteral::HasValue { owner, .. } => bound.contains(owner),
+                    ClauseLiteral::Links { relation, player, .. } => bound.contains(relation) || bound.contains(player),
+                    _ => false,
+                })
+                .unwrap_or(0);
+            let literal = remaining.remove(next);
+            match literal {
+                ClauseLiteral::Isa { instance, .. } => bound.extend([instance]),
+                ClauseLiteral::Has { owner, attribute, .. } => bound.extend([owner, attribute]),
+                ClauseLiteral::HasValue { owner, .. } => bound.extend([owner]),
+                ClauseLiteral::Links { relation, player, .. } => bound.extend([relation, player]),
+                _ => {}
+            }
+            ordered.push(literal);
+        }
+        ordered
+    }
+
+    // The rows extending the given ones, which satisfy every literal.
+    fn evaluate(&self, literals: &[&ClauseLiteral], mut rows: Vec<Row>) -> Result<Vec<Row>, typedb_driver::Error> {
+        for literal in literals {
+            let mut extended = Vec::new();
+            for row in rows {
+                extended.extend(self.extend(row, literal)?);
+            }
+            rows = extended;
+        }
+        Ok(rows)
+    }
+
+    // Whether some extension of the row satisfies every literal. Depth-first, stopping at the first.
+    fn exists(&self, literals: &[&ClauseLiteral], row: Row) -> Result<bool, typedb_driver::Error> {
+        let Some((literal, rest)) = literals.split_first() else {
+            return Ok(true);
+        };
+        for extended in self.extend(row, literal)? {
+            if self.exists(rest, extended)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn extend(&self, row: Row, literal: &ClauseLiteral) -> Result<Vec<Row>, typedb_driver::Error> {
+        let bind = |row: &Row, bindings: &[(&ClauseVariable, Binding)]| -> Option<Row> {
+            let mut extended = row.clone();
+            for (var, binding) in bindings {
+                match extended.get(*var) {
+                    Some(existing) if existing != binding => return None,
+                    Some(_) => {}
+                    None => {
+                        extended.insert((*var).clone(), binding.clone());
+                    }
+                }
+            }
+            Some(extended)
+        };
+        let rows = match literal {
+            ClauseLiteral::Isa { instance, type_ } => match row.get(instance) {
+                Some(Binding::Instance(iid)) => {
+                    if self.is_a(&self.types[iid], type_) {
+                        vec![row]
+                    } else {
+                        Vec::new()
+                    }
+                }
+                Some(Binding::Attribute(attribute_type, _)) => {
+                    if self.is_a(attribute_type, type_) {
+                        vec![row]
+                    } else {
+                        Vec::new()
+                    }
+                }
+                None => self
+                    .instances
+                    .iter()
+                    .filter(|(_, t)| self.is_a(t, type_))
+                    .filter_map(|(iid, _)| bind(&row, &[(instance, Binding::Instance(iid.clone()))]))
+                    .collect(),
+            },
+            ClauseLiteral::Has { owner, type_, attribute } => self
+                .ownerships_matching(&row, owner, type_)
+                .filter_map(|(iid, (t, value))| {
+                    bind(
+                        &row,
+                        &[
+                            (owner, Binding::Instance(iid.clone())),
+                            (attribute, Binding::Attribute(t.clone(), value.clone())),
+                        ],
+                    )
+                })
+                .collect(),
+            ClauseLiteral::HasValue { owner, type_, value } => self
+                .ownerships_matching(&row, owner, type_)
+                .filter(|(_, (_, owned))| compare(owned, value) == Some(Ordering::Equal))
+                .filter_map(|(iid, _)| bind(&row, &[(owner, Binding::Instance(iid.clone()))]))
+                .collect(),
+            ClauseLiteral::Links { relation, role, player } => {
+                let links: Vec<(IID, &SchemaType, IID)> = match (row.get(relation), row.get(player)) {
+                    (Some(Binding::Instance(iid)), _) => self
+                        .links_by_relation
+                        .get(iid)
+                        .into_iter()
+                        .flatten()
+                        .map(|(r, p)| (iid.clone(), r, p.clone()))
+                        .collect(),
+                    (_, Some(Binding::Instance(iid))) => self
+                        .links_by_player
+                        .get(iid)
+                        .into_iter()
+                        .flatten()
+                        .map(|(r, rel)| (rel.clone(), r, iid.clone()))
+                        .collect(),
+                    _ => self
+                        .links_by_relation
+                        .iter()
+                        .flat_map(|(rel, links)| links.iter().map(move |(r, p)| (rel.clone(), r, p.clone())))
+                        .collect(),
+                };
+                links
+                    .into_iter()
+                    .filter(|(_, r, _)| self.is_a(r, role))
+                    .filter_map(|(rel, _, p)| {
+                        bind(&row, &[(relation, Binding::Instance(rel)), (player, Binding::Instance(p))])
+                    })
+                    .collect()
+            }
+            ClauseLiteral::CompareVariables { lhs, comparator, rhs } => {
+                let (lhs_value, rhs_value) = (Self::value_of(&row, lhs)?, Self::value_of(&row, rhs)?);
+                if satisfies(compare(lhs_value, rhs_value), comparator) { vec![row] } else { Vec::new() }
+            }
+            ClauseLiteral::CompareConstant { lhs, comparator, rhs } => {
+                if satisfies(compare(Self::value_of(&row, lhs)?, rhs), comparator) { vec![row] } else { Vec::new() }
+            }
+            ClauseLiteral::Not { conjunction } => {
+                if !self.exists(&Self::ordered(conjunction, row.keys().collect()), row.clone())? {
+                    vec![row]
+                } else {
+                    Vec::new()
+                }
+            }
+            ClauseLiteral::Or { branches } => {
+                let mut rows = Vec::new();
+                for branch in branches {
+                    rows.extend(self.evaluate(&Self::ordered(branch, row.keys().collect()), vec![row.clone()])?);
+                }
+                rows
+            }
+            ClauseLiteral::Call { function, .. } => {
+                return Err(typedb_driver::Error::Other(format!(
+                    "The in-memory store cannot evaluate calls to functions, such as {function}"
+                )));
+            }
+        };
+        Ok(rows)
+    }
+
+    // Attributes of the type (or its subtypes) owned by the owner, or by any instance if it is unbound
+    fn ownerships_matching<'a>(
+        &'a self,
+        row: &Row,
+        owner: &ClauseVariable,
+        type_: &'a SchemaType,
+    ) -> impl Iterator<Item = (&'a IID, &'a (SchemaType, Value))> + 'a {
+        let owners: Vec<&IID> = match row.get(owner) {
+            Some(Binding::Instance(iid)) => {
+                self.ownerships.get_key_value(iid).map(|(iid, _)| iid).into_iter().collect()
+            }
+            Some(Binding::Attribute(..)) => Vec::new(),
+            None => self.ownerships.keys().collect(),
+        };
+        owners
+            .into_iter()
+            .flat_map(move |iid| self.attributes_of(iid).map(move |ownership| (iid, ownership)))
+            .filter(move |(_, (t, _))| self.is_a(t, type_))
+    }
+
+    fn value_of<'a>(row: &'a Row, var: &ClauseVariable) -> Result<&'a Value, typedb_driver::Error> {
+        match row.get(var) {
+            Some(Binding::Attribute(_, value)) => Ok(value),
+            _ => Err(Self::error(format!("Expected {var} to be bound to an attribute"))),
+        }
+    }
+
+    // The linked instances with their type and role labels, for Neighbourhood
+    fn linked(
+        &self,
+        links: Option<&Vec<(SchemaType, IID)>>,
+    ) -> Result<Vec<(IID, String, String)>, typedb_driver::Error> {
+        links
+            .into_iter()
+            .flatten()
+            .map(|(role, iid)| {
+                let type_ = self.types.get(iid).ok_or_else(|| Self::error(format!("Expected {iid} to be inserted")))?;
+                Ok((iid.clone(), type_.label().to_owned(), role.label().to_owned()))
+            })
+            .collect()
+    }
+
+    fn error(message: String) -> typedb_driver::Error {
+        typedb_driver::Error::Other(message)
+    }
+}
+
+impl CoverageOracle for FactStore {
+    fn test_clause_with_functions(
+        &self,
+        clause: &Clause,
+        functions: &[String],
+    ) -> Result<HashSet<Instance>, typedb_driver::Error> {
+        if !functions.is_empty() {
+            return Err(typedb_driver::Error::Other("The in-memory store cannot define functions".to_owned()));
+        }
+        // Bind the head breadth-first, then check each binding of it has some extension satisfying the rest.
+        let literals = Self::ordered(clause.literals(), HashSet::new());
+        let head = clause.head();
+        let mut rows = vec![Row::new()];
+        let mut bound = 0;
+        while bound < literals.len() && rows.first().is_some_and(|row| !head.iter().all(|var| row.contains_key(var))) {
+            rows = self.evaluate(&literals[bound..=bound], rows)?;
+            bound += 1;
+        }
+        let mut covered = HashSet::new();
+        for row in rows {
+            let instance = Instance::new(
+                head.iter()
+                    .map(|var| match row.get(var) {
+                        Some(Binding::Instance(iid)) => Ok(iid.clone()),
+                        Some(Binding::Attribute(..)) => {
+                            Err(Self::error(format!("Expected {var} to be bound to an entity or relation")))
+                        }
+                        None => Err(Self::error(format!("Expected {var} to be bound by a literal of the clause"))),
+                    })
+                    .collect::<Result<_, _>>()?,
+            );
+            if !covered.contains(&instance) && self.exists(&literals[bound..], row)? {
+                covered.insert(instance);
+            }
+        }
+        Ok(covered)
+    }
+}
+
+impl Neighbourhood for FactStore {
+    fn attributes_of(&self, iid: &IID) -> Result<Vec<(String, Value)>, typedb_driver::Error> {
+        Ok(self.attributes_of(iid).map(|(type_, value)| (type_.label().to_owned(), value.clone())).collect())
+    }
+
+    fn relations_of(&self, iid: &IID) -> Result<Vec<(IID, String, String)>, typedb_driver::Error> {
+        self.linked(self.links_by_player.get(iid))
+    }
+
+    fn players_of(&self, iid: &IID) -> Result<Vec<(IID, String, String)>, typedb_driver::Error> {
+        self.linked(self.links_by_relation.get(iid))
+    }
+}
+
+// Numbers compare across integer and double, other values only with the same value type.
+fn compare(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+    let as_f64 = |value: &Value| match value {
+        Value::Integer(integer) => Some(*integer as f64),
+        Value::Double(double) => Some(*double),
+        _ => None,
+    };
+    match (lhs, rhs) {
+        (Value::String(lhs), Value::String(rhs)) => Some(lhs.cmp(rhs)),
+        (Value::Boolean(lhs), Value::Boolean(rhs)) => Some(lhs.cmp(rhs)),
+        _ => match (as_f64(lhs), as_f64(rhs)) {
+            (Some(lhs), Some(rhs)) => lhs.partial_cmp(&rhs),
+            _ if lhs == rhs => Some(Ordering::Equal),
+            _ => None,
+        },
+    }
+}
+
+fn satisfies(ordering: Option<Ordering>, comparator: &ValueComparator) -> bool {
+    let Some(ordering) = ordering else {
+        return matches!(comparator, ValueComparator::Neq);
+    };
+    match comparator {
+        ValueComparator::Eq => ordering == Ordering::Equal,
+        ValueComparator::Neq => ordering != Ordering::Equal,
+        ValueComparator::Lt => ordering == Ordering::Less,
+        ValueComparator::Lte => ordering != Ordering::Greater,
+        ValueComparator::Gte => ordering != Ordering::Less,
+        ValueComparator::Gt => ordering == Ordering::Greater,
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use typedb_driver::{
+    IID, Promise,
+    concept::{Concept, Value},
+};
+
+use crate::{
+    CoverageOracle, Instance, TypeDBHelper,
+    clause::{Clause, ClauseLiteral, ClauseVariable, ValueComparator},
+    foil::FoilLearningTask,
+    language::SchemaType,
+};
+
+// Progol/Aleph: Each clause is learnt from a seed positive example. Its bottom clause describes the neighbourhood
+// of the seed up to max_depth, and refinements only add literals of the bottom clause instead of the whole language.
+// Any clause built this way covers the seed. Seeds are the first uncovered positive, by IID.
+pub struct ProgolLearningTask<O: Neighbourhood = TypeDBHelper> {
+    pub foil: FoilLearningTask<O>, // Examples, hypothesis language and the clause search itself.
+    pub max_depth: usize,          // Hops walked from the seed. Going from a player to its relation is one hop.
+}
+
+// The data around an instance, which bottom clauses are built from. Backed by TypeDB, or by memory::FactStore.
+pub trait Neighbourhood: CoverageOracle {
+    // The type label and value of each attribute the instance owns
+    fn attributes_of(&self, iid: &IID) -> Result<Vec<(String, Value)>, typedb_driver::Error>;
+
+    // The IID and type label of each relation the instance plays in, with the label of its role
+    fn relations_of(&self, iid: &IID) -> Result<Vec<(IID, String, String)>, typedb_driver::Error>;
+
+    // The IID and type label of each player of the relation, with the label of its role
+    fn players_of(&self, iid: &IID) -> Result<Vec<(IID, String, String)>, typedb_driver::Error>;
+}
+
+impl<O: Neighbourhood> ProgolLearningTask<O> {
+    pub fn new(foil: FoilLearningTask<O>, max_depth: usize) -> Self {
+        Self { foil, max_depth }
+    }
+
+    pub fn deconstruct(self) -> FoilLearningTask<O> {
+        self.foil
+    }
+
+    pub fn search(&self) -> Result<Vec<Clause>, typedb_driver::Error> {
+        let foil = &self.foil;
+        foil.search_with(&foil.positive_examples, &foil.negative_examples, |positives, negatives, theory| {
+            let Some(seed) = positives.iter().min_by_key(|positive| positive.sort_key()) else {
+                return Ok(None);
+            };
+            let bottom = self.bottom_clause(seed)?;
+            println!("Bottom clause has {} literals", bottom.len());
+            foil.learn_clause_from(foil.initial_clause(), positives, negatives, theory, |clause| {
+                Self::refinements(clause, &bottom, &foil.language.schema)
+            })
+        })
+    }
+
+    // Adds each literal of the bottom clause which connects to the clause.
+    fn refinements(clause: &Clause, bottom: &Clause, schema: &crate::language::Schema) -> Vec<Clause> {
+        bottom
+            .literals()
+            .iter()
+            .filter(|literal| clause.connects(literal))
+            .filter_map(|literal| clause.extend_with_literal(literal, schema))
+            .collect()
+    }
+
+    // The most specific clause in the hypothesis language covering the seed. Each instance within max_depth
+    // becomes a variable with its exact type. Its attributes are added as the language allows: categorical ones
+    // with their values, numeric ones with the side of every threshold they fall on, comparable ones as variables.
+    pub fn bottom_clause(&self, seed: &Instance) -> Result<Clause, typedb_driver::Error> {
+        let language = &self.foil.language;
+        let schema = &language.schema;
+        let oracle = &self.foil.oracle;
+        let mut bottom = self.foil.initial_clause();
+        let add = |bottom: &mut Clause, literal: ClauseLiteral| {
+            if let Some(extended) = bottom.extend_with_literal(&literal, schema) {
+                *bottom = extended;
+            }
+        };
+
+        let mut variables: HashMap<String, ClauseVariable> = HashMap::new(); // By IID, or type and value for attributes
+        let mut queue = VecDeque::new();
+        for (var, iid) in bottom.head().to_vec().into_iter().zip(&seed.0) {
+            variables.insert(iid.to_string(), var.clone());
+            queue.push_back((var, iid.clone(), 0));
+        }
+        let mut visited = HashSet::new();
+        while let Some((var, iid, depth)) = queue.pop_front() {
+            if !visited.insert(iid.clone()) {
+                continue;
+            }
+            for (label, value) in oracle.attributes_of(&iid)? {
+                let type_ = language.lookup_type(&label).expect("Expected type to be in schema");
+                Self::attribute_literals(&var, &type_, &value, &mut variables, language)
+                    .into_iter()
+                    .for_each(|literal| add(&mut bottom, literal));
+            }
+            if depth >= self.max_depth {
+                continue;
+            }
+            let mut instance_var = |iid: IID, label: &str, depth: usize, bottom: &mut Clause| {
+                if let Some(var) = variables.get(&iid.to_string()) {
+                    return var.clone();
+                }
+                let type_ = language.lookup_type(label).expect("Expected type to be in schema");
+                let var = Self::variable(&type_, variables.len());
+                variables.insert(iid.to_string(), var.clone());
+                add(bottom, ClauseLiteral::Isa { instance: var.clone(), type_ });
+                queue.push_back((var.clone(), iid, depth));
+                var
+            };
+            for (relation, label, role) in oracle.relations_of(&iid)? {
+                let relation = instance_var(relation, &label, depth + 1, &mut bottom);
+                let role = Self::role_type(schema, &role);
+                add(&mut bottom, ClauseLiteral::Links { relation, role, player: var.clone() });
+            }
+            for (player, label, role) in oracle.players_of(&iid)? {
+                let player = instance_var(player, &label, depth + 1, &mut bottom);
+                let role = Self::role_type(schema, &role);
+                add(&mut bottom, ClauseLiteral::Links { relation: var.clone(), role, player });
+            }
+        }
+        Ok(bottom)
+    }
+
+    fn attribute_literals(
+        owner: &ClauseVariable,
+        type_: &SchemaType,
+        value: &Value,
+        variables: &mut HashMap<String, ClauseVariable>,
+        language: &crate::language::HypothesisLanguage,
+    ) -> Vec<ClauseLiteral> {
+        let schema = &language.schema;
+        let mut literals = Vec::new();
+        if schema.categorical_attribute_values.get(type_).is_some_and(|values| values.contains(value)) {
+            literals.push(ClauseLiteral::HasValue { owner: owner.clone(), type_: type_.clone(), value: value.clone() });
+        }
+        let thresholds = schema.numeric_attribute_thresholds.get(type_);
+        if thresholds.is_none() && !language.comparable_attribute_types.contains(type_) {
+            return literals;
+        }
+        // Attributes with the same value are the same instance, so they share a variable.
+        let key = format!("{type_} {value}");
+        let next_index = variables.len();
+        let attribute = variables.entry(key).or_insert_with(|| Self::variable(type_, next_index)).clone();
+        literals.push(ClauseLiteral::Has { owner: owner.clone(), type_: type_.clone(), attribute: attribute.clone() });
+        for threshold in thresholds.into_iter().flatten() {
+            let comparator =
+                if as_f64(value) <= as_f64(threshold) { ValueComparator::Lte } else { ValueComparator::Gt };
+            literals.push(ClauseLiteral::CompareConstant {
+                lhs: attribute.clone(),
+                comparator,
+                rhs: threshold.clone(),
+            });
+        }
+        literals
+    }
+
+    fn role_type(schema: &crate::language::Schema, label: &str) -> SchemaType {
+        schema.related_by.keys().find(|role| role.label() == label).expect("Expected role to be in schema").clone()
+    }
+
+    fn variable(type_: &SchemaType, index: usize) -> ClauseVariable {
+        ClauseVariable(format!("{}_b{}", type_.label().replace(":", "__"), index))
+    }
+}
+
+impl Neighbourhood for TypeDBHelper {
+    fn attributes_of(&self, iid: &IID) -> Result<Vec<(String, Value)>, typedb_driver::Error> {
+        let attributes = self.neighbours(iid, "$x has $a;")?;
+        Ok(attributes
+            .into_iter()
+            .map(|(label, attribute, _)| (label, attribute.try_get_value().unwrap().clone()))
+            .collect())
+    }
+
+    fn relations_of(&self, iid: &IID) -> Result<Vec<(IID, String, String)>, typedb_driver::Error> {
+        self.linked(iid, "$a links ($role: $x);")
+    }
+
+    fn players_of(&self, iid: &IID) -> Result<Vec<(IID, String, String)>, typedb_driver::Error> {
+        self.linked(iid, "$x links ($role: $a);")
+    }
+}
+
+impl TypeDBHelper {
+    // The concepts bound to $a, and $role if the pattern mentions it, for the instance with the IID as $x.
+    fn neighbours(
+        &self,
+        iid: &IID,
+        pattern: &str,
+    ) -> Result<Vec<(String, Concept, Option<Concept>)>, typedb_driver::Error> {
+        let select = if pattern.contains("$role") { "$a, $role" } else { "$a" };
+        self.read_tx()?
+            .query(format!("match $x iid {iid}; {pattern} select {select};"))
+            .resolve()?
+            .into_rows()
+            .map(|row| {
+                let row = row?;
+                let role = if select.contains("$role") { row.get("role").unwrap().cloned() } else { None };
+                let concept = row.get("a").unwrap().unwrap().clone();
+                Ok((concept.get_label().to_owned(), concept, role))
+            })
+            .collect()
+    }
+
+    fn linked(&self, iid: &IID, pattern: &str) -> Result<Vec<(IID, String, String)>, typedb_driver::Error> {
+        let neighbours = self.neighbours(iid, pattern)?;
+        Ok(neighbours
+            .into_iter()
+            .map(|(label, concept, role)| {
+                (concept.try_get_iid().unwrap().clone(), label, role.unwrap().get_label().to_owned())
+            })
+            .collect())
+    }
+}
+
+fn as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Integer(integer) => *integer as f64,
+        Value::Double(double) => *double,
+        _ => f64::NAN,
+    }
+}
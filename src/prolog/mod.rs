@@ -0,0 +1,534 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+
+use itertools::Itertools;
+
+use crate::{
+    foil::{FoilConfig, FoilLearningTask},
+    language::LanguageDiscoveryOption,
+    memory::FactStore,
+    prolog::parser::{Sentence, parse_program},
+    tilde::{classification::ClassificationTask, tilde::TildeLearningTask, tree::TildeConfig},
+};
+
+mod parser;
+
+pub use parser::Term;
+
+type Result<T> = std::result::Result<T, typedb_driver::Error>;
+
+// Aleph/Progol style mode declarations: `+type` and `-type` arguments are instances, `#type` ones are constants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeMarker {
+    Input,
+    Output,
+    Constant,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mode {
+    pub predicate: String,
+    pub args: Vec<(ModeMarker, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fact {
+    pub predicate: String,
+    pub args: Vec<Term>,
+}
+
+// An ILP benchmark in Prolog: background facts and mode declarations (e.g. Aleph's .b file), and the positive and
+// negative examples of a unary target predicate (.f and .n files). Rules in the background are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct PrologDataset {
+    pub facts: Vec<Fact>,
+    pub head_modes: Vec<Mode>,
+    pub body_modes: Vec<Mode>,
+    pub positives: Vec<Fact>,
+    pub negatives: Vec<Fact>,
+}
+
+// What each argument of a predicate becomes
+#[derive(Debug, Clone, PartialEq)]
+enum Argument {
+    Instance(String),  // A player of the entity type, by its label before renaming
+    Attribute(String), // A value of the attribute type
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum AttributeValueType {
+    Integer,
+    Double,
+    String,
+}
+
+// The dataset converted to TypeQL, with what is needed to learn the target predicate from it.
+#[derive(Debug, Clone)]
+pub struct PrologImport {
+    pub schema: String, // A `define` query
+    pub data: String,   // An `insert` query
+    pub target_type_label: String,
+    pub class_attribute_label: String, // Boolean: true for positive examples, false for negative ones
+    pub categorical_attribute_labels: Vec<String>,
+    pub numeric_attribute_labels: Vec<String>,
+}
+
+impl PrologDataset {
+    // The entity type of instances without a mode declaration
+    const OBJECT_TYPE: &'static str = "object";
+
+    pub fn from_files(background: &Path, positives: &Path, negatives: &Path) -> Result<Self> {
+        let read = |path: &Path| {
+            std::fs::read_to_string(path)
+                .map_err(|err| typedb_driver::Error::Other(format!("Could not read {}: {err}", path.display())))
+        };
+        let mut dataset = Self::default();
+        dataset.add_background(&read(background)?)?;
+        dataset.positives.extend(Self::parse_facts(&read(positives)?)?);
+        dataset.negatives.extend(Self::parse_facts(&read(negatives)?)?);
+        Ok(dataset)
+    }
+
+    // Facts, and `modeh`/`modeb` declarations. Other directives are ignored.
+    pub fn add_background(&mut self, prolog: &str) -> Result<()> {
+        for sentence in parse_program(prolog)? {
+            match sentence {
+                Sentence::Fact(term) => self.facts.push(Self::fact(term)?),
+                Sentence::Directive(Term::Compound(name, args)) if name == "modeh" || name == "modeb" => {
+                    let mode = Self::mode(args.last())?;
+                    if name == "modeh" { self.head_modes.push(mode) } else { self.body_modes.push(mode) }
+                }
+                Sentence::Directive(_) => {}
+                Sentence::Skipped { line } => println!("Skipping the rule or directive on line {line}"),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse_facts(prolog: &str) -> Result<Vec<Fact>> {
+        parse_program(prolog)?
+            .into_iter()
+            .filter_map(|sentence| match sentence {
+                Sentence::Fact(term) => Some(Self::fact(term)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn fact(term: Term) -> Result<Fact> {
+        match term {
+            Term::Atom(predicate) => Ok(Fact { predicate, args: Vec::new() }),
+            Term::Compound(predicate, args) if args.iter().all(Term::is_constant) => Ok(Fact { predicate, args }),
+            term => Err(typedb_driver::Error::Other(format!("Expected a fact over constants, found {term}"))),
+        }
+    }
+
+    fn mode(term: Option<&Term>) -> Result<Mode> {
+        let Some(Term::Compound(predicate, args)) = term else {
+            return Err(typedb_driver::Error::Other(format!("Expected a mode declaration, found {term:?}")));
+        };
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                Term::Compound(marker, type_) if type_.len() == 1 => {
+                    let marker = match marker.as_str() {
+                        "+" => ModeMarker::Input,
+                        "-" => ModeMarker::Output,
+                        "#" => ModeMarker::Constant,
+                        _ => {
+                            return Err(typedb_driver::Error::Other(format!(
+                                "Expected +type, -type or #type, found {arg}"
+                            )));
+                        }
+                    };
+                    Ok((marker, type_[0].to_string()))
+                }
+                _ => Err(typedb_driver::Error::Other(format!("Expected +type, -type or #type, found {arg}"))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Mode { predicate: predicate.clone(), args })
+    }
+
+    // An untyped (object) instance takes the type of a typed argument, unless it is one of the first num_fixed
+    fn add_instance(
+        instances: &mut Vec<(String, String)>,
+        instance_index: &mut HashMap<String, usize>,
+        constant: &Term,
+        type_: &str,
+        num_fixed: usize,
+    ) -> Result<()> {
+        let constant = constant.to_string();
+        let Some(&index) = instance_index.get(&constant) else {
+            instance_index.insert(constant.clone(), instances.len());
+            instances.push((constant, type_.to_owned()));
+            return Ok(());
+        };
+        let existing = &mut instances[index].1;
+        if *existing == Self::OBJECT_TYPE && index >= num_fixed {
+            *existing = type_.to_owned();
+        } else if existing != type_ && existing != Self::OBJECT_TYPE && type_ != Self::OBJECT_TYPE {
+            return Err(typedb_driver::Error::Other(format!(
+                "Expected {constant} to have one type, found {existing} and {type_}"
+            )));
+        }
+        Ok(())
+    }
+
+    // Unary predicates become entity types, subtypes of the type of their argument, unless some constant already has
+    // such a type: then, like other predicates, they become relations with positional roles `arg1`, `arg2`, ...
+    // Constant arguments (`#type`, or numbers) become attributes, owned by the relation.
+    pub fn to_typeql(&self) -> Result<PrologImport> {
+        let target = match self.head_modes.first() {
+            Some(mode) => (mode.predicate.clone(), mode.args.len()),
+            None => self
+                .positives
+                .first()
+                .map(|fact| (fact.predicate.clone(), fact.args.len()))
+                .ok_or_else(|| typedb_driver::Error::Other("Expected a modeh declaration or examples".to_owned()))?,
+        };
+        if target.1 != 1 {
+            return Err(typedb_driver::Error::Other(format!(
+                "Expected a unary target predicate, found {}/{}",
+                target.0, target.1
+            )));
+        }
+        if let Some(example) = self
+            .positives
+            .iter()
+            .chain(&self.negatives)
+            .find(|example| (example.predicate.as_str(), example.args.len()) != (target.0.as_str(), target.1))
+        {
+            return Err(typedb_driver::Error::Other(format!(
+                "Expected examples of {}/{}, found {}/{}",
+                target.0,
+                target.1,
+                example.predicate,
+                example.args.len()
+            )));
+        }
+        let target_type = match self.head_modes.first() {
+            Some(mode) => mode.args[0].1.clone(),
+            None => Self::OBJECT_TYPE.to_owned(),
+        };
+
+        // Background facts by predicate, in the order the predicates first appear
+        let mut facts_by_predicate: Vec<((String, usize), Vec<&Fact>)> = Vec::new();
+        for fact in self.facts.iter().filter(|fact| (fact.predicate.clone(), fact.args.len()) != target) {
+            let key = (fact.predicate.clone(), fact.args.len());
+            match facts_by_predicate.iter_mut().find(|(predicate, _)| *predicate == key) {
+                Some((_, facts)) => facts.push(fact),
+                None => facts_by_predicate.push((key, vec![fact])),
+            }
+        }
+        let mut arguments: HashMap<(String, usize), Vec<Argument>> = HashMap::new();
+        for ((predicate, arity), facts) in &facts_by_predicate {
+            let mode = self.body_modes.iter().find(|mode| mode.predicate == *predicate && mode.args.len() == *arity);
+            let predicate_arguments = (0..*arity)
+                .map(|i| {
+                    let numeric = facts.iter().all(|fact| fact.args[i].is_number());
+                    match mode.map(|mode| &mode.args[i]) {
+                        Some((ModeMarker::Constant, type_)) => Argument::Attribute(type_.clone()),
+                        Some((_, type_)) if numeric => Argument::Attribute(type_.clone()),
+                        Some((_, type_)) => Argument::Instance(type_.clone()),
+                        None if numeric => Argument::Attribute(format!("{predicate}-arg{}", i + 1)),
+                        None => Argument::Instance(Self::OBJECT_TYPE.to_owned()),
+                    }
+                })
+                .collect();
+            arguments.insert((predicate.clone(), *arity), predicate_arguments);
+        }
+
+        // Instances, by constant, with the type of the arguments they appear in. Examples keep the target type.
+        let mut instances: Vec<(String, String)> = Vec::new();
+        let mut instance_index: HashMap<String, usize> = HashMap::new();
+        for example in self.positives.iter().chain(&self.negatives) {
+            Self::add_instance(&mut instances, &mut instance_index, &example.args[0], &target_type, 0)?;
+        }
+        let num_examples = instances.len();
+        for (key, facts) in &facts_by_predicate {
+            for fact in facts {
+                for (arg, argument) in fact.args.iter().zip(&arguments[key]) {
+                    if let Argument::Instance(type_) = argument {
+                        Self::add_instance(&mut instances, &mut instance_index, arg, type_, num_examples)?;
+                    }
+                }
+            }
+        }
+
+        // Unary predicates which become types, and the type of each instance they apply to
+        let mut unary_types: Vec<(String, String)> = Vec::new(); // predicate and supertype
+        let mut instance_types: HashMap<String, String> = HashMap::new();
+        for ((predicate, arity), facts) in &facts_by_predicate {
+            if *arity != 1 || !matches!(arguments[&(predicate.clone(), *arity)][0], Argument::Instance(_)) {
+                continue;
+            }
+            let constants: HashSet<String> = facts.iter().map(|fact| fact.args[0].to_string()).collect();
+            // The supertype is the type of every instance, so it needs them all to have the same type
+            let supertypes: Vec<&String> =
+                constants.iter().map(|constant| &instances[instance_index[constant]].1).unique().collect();
+            if let [supertype] = supertypes[..]
+                && constants.iter().all(|constant| !instance_types.contains_key(constant))
+            {
+                unary_types.push((predicate.clone(), supertype.clone()));
+                instance_types.extend(constants.into_iter().map(|constant| (constant, predicate.clone())));
+            }
+        }
+        let relations: Vec<&(String, usize)> = facts_by_predicate
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|(predicate, arity)| *arity > 1 || !unary_types.iter().any(|(unary, _)| unary == predicate))
+            .filter(|key| {
+                let connected = arguments[*key].iter().any(|argument| matches!(argument, Argument::Instance(_)));
+                if !connected {
+                    println!("Skipping {}/{}, which has no arguments to be instances", key.0, key.1);
+                }
+                connected
+            })
+            .collect();
+
+        let mut value_types: HashMap<&str, AttributeValueType> = HashMap::new();
+        for key in &relations {
+            let facts = &facts_by_predicate.iter().find(|(predicate, _)| predicate == *key).unwrap().1;
+            for (i, argument) in arguments[*key].iter().enumerate() {
+                let Argument::Attribute(label) = argument else {
+                    continue;
+                };
+                let value_type = facts
+                    .iter()
+                    .map(|fact| match fact.args[i] {
+                        Term::Integer(_) => AttributeValueType::Integer,
+                        Term::Float(_) => AttributeValueType::Double,
+                        _ => AttributeValueType::String,
+                    })
+                    .max()
+                    .unwrap_or(AttributeValueType::String);
+                let entry = value_types.entry(label).or_insert(value_type);
+                *entry = (*entry).max(value_type);
+            }
+        }
+
+        // Labels, renamed if they are not valid TypeQL labels or would name two types
+        let mut labels = Labels::default();
+        let entity_types: Vec<&String> = instances.iter().map(|(_, type_)| type_).unique().collect();
+        for type_ in &entity_types {
+            labels.assign("entity", type_);
+        }
+        for (predicate, _) in &unary_types {
+            labels.assign("type", predicate);
+        }
+        for (predicate, _) in &relations {
+            labels.assign("relation", predicate);
+        }
+        for attribute in value_types.keys().sorted() {
+            labels.assign("attribute", attribute);
+        }
+        let class_label = labels.assign("attribute", &target.0);
+
+        let mut schema = String::from("define\n");
+        let mut plays: HashMap<&str, BTreeSet<String>> = HashMap::new();
+        for key in &relations {
+            let relation = labels.get("relation", &key.0);
+            let mut owns = BTreeSet::new();
+            for argument in &arguments[*key] {
+                if let Argument::Attribute(attribute) = argument {
+                    owns.insert(labels.get("attribute", attribute));
+                }
+            }
+            // Roles are played by the types of the instances which play them
+            let facts = &facts_by_predicate.iter().find(|(predicate, _)| predicate == *key).unwrap().1;
+            for fact in facts {
+                for (i, (arg, argument)) in fact.args.iter().zip(&arguments[*key]).enumerate() {
+                    if let Argument::Instance(_) = argument {
+                        let type_ = &instances[instance_index[&arg.to_string()]].1;
+                        plays.entry(type_).or_default().insert(format!("{relation}:arg{}", i + 1));
+                    }
+                }
+            }
+            let roles = arguments[*key]
+                .iter()
+                .enumerate()
+                .filter(|(_, argument)| matches!(argument, Argument::Instance(_)))
+                .map(|(i, _)| format!(", relates arg{}", i + 1))
+                .join("");
+            let owns = owns.iter().map(|attribute| format!(", owns {attribute}")).join("");
+            writeln!(schema, "relation {relation}{roles}{owns};").unwrap();
+        }
+        for type_ in &entity_types {
+            let plays = plays.get(type_.as_str()).into_iter().flatten().map(|role| format!(", plays {role}")).join("");
+            let owns = if **type_ == target_type { format!(", owns {class_label}") } else { String::new() };
+            writeln!(schema, "entity {}{plays}{owns};", labels.get("entity", type_)).unwrap();
+        }
+        for (predicate, supertype) in &unary_types {
+            writeln!(schema, "entity {}, sub {};", labels.get("type", predicate), labels.get("entity", supertype))
+                .unwrap();
+        }
+        let mut categorical_attribute_labels = Vec::new();
+        let mut numeric_attribute_labels = Vec::new();
+        for (attribute, value_type) in value_types.iter().sorted() {
+            let label = labels.get("attribute", attribute);
+            let value_type = match value_type {
+                AttributeValueType::Integer => "integer",
+                AttributeValueType::Double => "double",
+                AttributeValueType::String => "string",
+            };
+            writeln!(schema, "attribute {label}, value {value_type};").unwrap();
+            match value_type {
+                "string" => categorical_attribute_labels.push(label),
+                _ => numeric_attribute_labels.push(label),
+            }
+        }
+        writeln!(schema, "attribute {class_label}, value boolean;").unwrap();
+
+        let mut data = String::from("insert\n");
+        let classes: HashMap<String, bool> = self
+            .negatives
+            .iter()
+            .map(|example| (example.args[0].to_string(), false))
+            .chain(self.positives.iter().map(|example| (example.args[0].to_string(), true)))
+            .collect();
+        for (i, (constant, type_)) in instances.iter().enumerate() {
+            let type_ = match instance_types.get(constant) {
+                Some(predicate) => labels.get("type", predicate),
+                None => labels.get("entity", type_),
+            };
+            let class = classes.get(constant).map(|class| format!(", has {class_label} {class}")).unwrap_or_default();
+            writeln!(data, "$i{i} isa {type_}{class};").unwrap();
+        }
+        let mut relation_count = 0;
+        for key in &relations {
+            let relation = labels.get("relation", &key.0);
+            let facts = &facts_by_predicate.iter().find(|(predicate, _)| predicate == *key).unwrap().1;
+            for fact in facts {
+                let mut role_players = Vec::new();
+                let mut ownerships = String::new();
+                for (i, (arg, argument)) in fact.args.iter().zip(&arguments[*key]).enumerate() {
+                    match argument {
+                        Argument::Instance(_) => {
+                            role_players.push(format!("arg{}: $i{}", i + 1, instance_index[&arg.to_string()]))
+                        }
+                        Argument::Attribute(attribute) => {
+                            let value = match (value_types[attribute.as_str()], arg) {
+                                (AttributeValueType::String, arg) => format!("\"{}\"", escape(&arg.to_string())),
+                                (AttributeValueType::Double, Term::Integer(integer)) => {
+                                    format!("{:?}", *integer as f64)
+                                }
+                                (_, arg) => arg.to_string(),
+                            };
+                            write!(ownerships, ", has {} {value}", labels.get("attribute", attribute)).unwrap();
+                        }
+                    }
+                }
+                writeln!(data, "$r{relation_count} isa {relation}, links ({}){ownerships};", role_players.join(", "))
+                    .unwrap();
+                relation_count += 1;
+            }
+        }
+
+        Ok(PrologImport {
+            schema,
+            data,
+            target_type_label: labels.get("entity", &target_type),
+            class_attribute_label: class_label,
+            categorical_attribute_labels,
+            numeric_attribute_labels,
+        })
+    }
+}
+
+impl PrologImport {
+    pub fn fact_store(&self) -> Result<FactStore> {
+        FactStore::from_typeql(&self.schema, &self.data)
+    }
+
+    // Constants as categorical attributes, and numbers compared against at most max_thresholds thresholds.
+    pub fn language_options(&self, max_thresholds: usize) -> Vec<LanguageDiscoveryOption> {
+        vec![
+            LanguageDiscoveryOption::CategoricalAttributes { type_labels: self.categorical_attribute_labels.clone() },
+            LanguageDiscoveryOption::NumericAttributes {
+                type_labels: self.numeric_attribute_labels.clone(),
+                max_thresholds,
+            },
+        ]
+    }
+
+    pub fn foil_task(
+        &self,
+        options: &[LanguageDiscoveryOption],
+        config: FoilConfig,
+    ) -> Result<FoilLearningTask<FactStore>> {
+        let store = self.fact_store()?;
+        let language = store.language(options);
+        let examples_by_class = store.examples_by_class(&self.target_type_label, &self.class_attribute_label);
+        FoilLearningTask::from_examples(
+            store,
+            language,
+            self.target_type_label.clone(),
+            self.class_attribute_label.clone(),
+            examples_by_class,
+            config,
+        )
+    }
+
+    pub fn tilde_task(
+        &self,
+        options: &[LanguageDiscoveryOption],
+        config: TildeConfig,
+    ) -> Result<TildeLearningTask<ClassificationTask, FactStore>> {
+        let store = self.fact_store()?;
+        let language = store.language(options);
+        let examples_by_class = store.examples_by_class(&self.target_type_label, &self.class_attribute_label);
+        let classification = ClassificationTask::from_examples(
+            &language,
+            &self.target_type_label,
+            &self.class_attribute_label,
+            &examples_by_class,
+        );
+        Ok(TildeLearningTask::new(store, language, classification, config))
+    }
+}
+
+// TypeQL labels for Prolog names, by the kind of type they name. A name which is not a valid label is sanitised,
+// and one which is taken by another kind of type, or is a TypeQL keyword, gets the kind as a suffix, then a number
+// if that is taken too.
+#[derive(Default)]
+struct Labels {
+    assigned: HashMap<(&'static str, String), String>,
+    taken: HashSet<String>,
+}
+
+impl Labels {
+    fn assign(&mut self, kind: &'static str, name: &str) -> String {
+        if let Some(label) = self.assigned.get(&(kind, name.to_owned())) {
+            return label.clone();
+        }
+        let mut label: String =
+            name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '-' }).collect();
+        if !label.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            label = format!("{kind}-{label}");
+        }
+        if self.taken.contains(&label) || typeql::is_reserved_keyword(&label) {
+            label = format!("{label}-{kind}");
+        }
+        let base = label.clone();
+        for n in 2.. {
+            if !self.taken.contains(&label) {
+                break;
+            }
+            label = format!("{base}-{n}");
+        }
+        self.taken.insert(label.clone());
+        self.assigned.insert((kind, name.to_owned()), label.clone());
+        label
+    }
+
+    fn get(&self, kind: &'static str, name: &str) -> String {
+        self.assigned[&(kind, name.to_owned())].clone()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
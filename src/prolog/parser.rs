@@ -0,0 +1,286 @@
+use std::fmt::{Display, Formatter};
+
+// The subset of Prolog terms found in ILP benchmark files. Prefix operators, as in the mode declaration `+drug`,
+// are compounds named by the operator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Atom(String),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Variable(String),
+    Compound(String, Vec<Term>),
+}
+
+impl Term {
+    pub fn is_number(&self) -> bool {
+        matches!(self, Term::Integer(_) | Term::Float(_))
+    }
+
+    pub fn is_constant(&self) -> bool {
+        !matches!(self, Term::Variable(_) | Term::Compound(..))
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Term::Atom(atom) | Term::String(atom) | Term::Variable(atom) => f.write_str(atom),
+            Term::Integer(integer) => write!(f, "{integer}"),
+            Term::Float(float) => write!(f, "{float:?}"),
+            Term::Compound(name, args) => {
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+pub(crate) enum Sentence {
+    Fact(Term),
+    Directive(Term),
+    // Rules, and directives using operators other than prefix ones, are skipped.
+    Skipped { line: usize },
+}
+
+pub(crate) fn parse_program(text: &str) -> Result<Vec<Sentence>, typedb_driver::Error> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+    let mut sentences = Vec::new();
+    loop {
+        parser.skip_layout();
+        if parser.peek().is_none() {
+            return Ok(sentences);
+        }
+        let start = parser.pos;
+        if parser.eat_str(":-") {
+            match parser.term().and_then(|term| parser.end().map(|_| term)) {
+                Ok(term) => sentences.push(Sentence::Directive(term)),
+                Err(_) => {
+                    parser.pos = start;
+                    parser.skip_sentence();
+                    sentences.push(Sentence::Skipped { line: parser.line_of(start) });
+                }
+            }
+            continue;
+        }
+        let term = parser.term()?;
+        parser.skip_layout();
+        if parser.eat_str(":-") {
+            parser.skip_sentence();
+            sentences.push(Sentence::Skipped { line: parser.line_of(start) });
+        } else {
+            parser.end()?;
+            sentences.push(Sentence::Fact(term));
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let eaten = s.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c));
+        if eaten {
+            self.pos += s.chars().count();
+        }
+        eaten
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        self.chars[..pos].iter().filter(|c| **c == '\n').count() + 1
+    }
+
+    fn error(&self, expected: &str) -> typedb_driver::Error {
+        let found = self.peek().map_or("end of input".to_owned(), |c| format!("'{c}'"));
+        typedb_driver::Error::Other(format!("Line {}: expected {expected}, found {found}", self.line_of(self.pos)))
+    }
+
+    // Whitespace and comments
+    fn skip_layout(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => self.pos += 1,
+                Some('%') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some('/') if self.peek_at(1) == Some('*') => {
+                    self.pos += 2;
+                    while self.peek().is_some() && !self.eat_str("*/") {
+                        self.pos += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // To after the full stop ending the sentence, which is followed by layout or the end of the input.
+    fn skip_sentence(&mut self) {
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\'' | '"' => {
+                    while self.peek().is_some_and(|q| q != c) {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                '.' if self.peek().is_none_or(|next| next.is_whitespace() || next == '%') => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn end(&mut self) -> Result<(), typedb_driver::Error> {
+        self.skip_layout();
+        if self.eat('.') { Ok(()) } else { Err(self.error("'.'")) }
+    }
+
+    fn term(&mut self) -> Result<Term, typedb_driver::Error> {
+        self.skip_layout();
+        match self.peek() {
+            Some('+' | '-') if self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) => self.number(),
+            Some(c @ ('+' | '-' | '#')) => {
+                self.pos += 1;
+                Ok(Term::Compound(c.to_string(), vec![self.term()?]))
+            }
+            Some(c) if c.is_ascii_digit() => self.number(),
+            Some('\'') => {
+                let name = self.quoted('\'')?;
+                self.compound_or_atom(name)
+            }
+            Some('"') => Ok(Term::String(self.quoted('"')?)),
+            Some(c) if c.is_lowercase() => {
+                let name = self.name();
+                self.compound_or_atom(name)
+            }
+            Some(c) if c.is_uppercase() || c == '_' => Ok(Term::Variable(self.name())),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Term::Atom("*".to_owned()))
+            }
+            Some('[') => {
+                self.pos += 1;
+                self.skip_layout();
+                let items = if self.eat(']') { Vec::new() } else { self.args(']')? };
+                Ok(Term::Compound("[]".to_owned(), items))
+            }
+            _ => Err(self.error("a term")),
+        }
+    }
+
+    fn compound_or_atom(&mut self, name: String) -> Result<Term, typedb_driver::Error> {
+        if self.eat('(') { Ok(Term::Compound(name, self.args(')')?)) } else { Ok(Term::Atom(name)) }
+    }
+
+    // Comma separated terms, up to and including the closing bracket
+    fn args(&mut self, close: char) -> Result<Vec<Term>, typedb_driver::Error> {
+        let mut args = vec![self.term()?];
+        loop {
+            self.skip_layout();
+            if self.eat(close) {
+                return Ok(args);
+            }
+            if !self.eat(',') {
+                return Err(self.error(&format!("',' or '{close}'")));
+            }
+            args.push(self.term()?);
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, typedb_driver::Error> {
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error(&format!("closing {quote}"))),
+                // A doubled quote, or an escaped character
+                Some(c) if c == quote && self.peek_at(1) == Some(quote) => {
+                    text.push(quote);
+                    self.pos += 2;
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') if self.peek_at(1).is_some() => {
+                    text.push(self.peek_at(1).unwrap());
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Term, typedb_driver::Error> {
+        let start = self.pos;
+        if matches!(self.peek(), Some('+' | '-')) {
+            self.pos += 1;
+        }
+        let digits = |parser: &mut Self| {
+            while parser.peek().is_some_and(|c| c.is_ascii_digit()) {
+                parser.pos += 1;
+            }
+        };
+        digits(self);
+        let mut float = false;
+        if self.peek() == Some('.') && self.peek_at(1).is_some_and(|c| c.is_ascii_digit()) {
+            float = true;
+            self.pos += 1;
+            digits(self);
+        }
+        if matches!(self.peek(), Some('e' | 'E'))
+            && (self.peek_at(1).is_some_and(|c| c.is_ascii_digit())
+                || (matches!(self.peek_at(1), Some('+' | '-')) && self.peek_at(2).is_some_and(|c| c.is_ascii_digit())))
+        {
+            float = true;
+            self.pos += 2;
+            digits(self);
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let text = text.strip_prefix('+').unwrap_or(&text);
+        if float {
+            text.parse().map(Term::Float).map_err(|_| self.error("a number"))
+        } else {
+            text.parse().map(Term::Integer).map_err(|_| self.error("a number"))
+        }
+    }
+}
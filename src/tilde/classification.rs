@@ -3,15 +3,20 @@ use std::{
     ops::AddAssign,
 };
 
+use itertools::Itertools;
 use typedb_driver::Promise;
 
 use crate::{
-    Instance, TypeDBHelper,
+    Instance, TypeDBHelper, class_label,
     clause::{Clause, ClauseVariable},
     language::{HypothesisLanguage, SchemaType},
+    tilde::{
+        tilde::TildeTask,
+        tree::{TildeDataset, partition_examples},
+    },
 };
 
-pub(super) type ExampleClassType = bool;
+pub(super) type ExampleClassType = String; // See crate::class_label
 #[derive(Clone)]
 pub struct Example {
     pub instance: Instance,
@@ -32,22 +37,31 @@ impl Dataset {
         counters
     }
 
-    pub(super) fn split_on(&self, mut included_instances: HashSet<Instance>) -> (Dataset, Dataset) {
-        included_instances.retain(|x| self.examples.iter().any(|e| &e.instance == x));
-        let mut left = Dataset { examples: Vec::with_capacity(included_instances.len()) };
-        let mut right = Dataset { examples: Vec::with_capacity(self.examples.len() - included_instances.len()) };
-        for e in &self.examples {
-            if included_instances.contains(&e.instance) {
-                left.examples.push(e.clone());
-            } else {
-                right.examples.push(e.clone());
-            }
-        }
-        (left, right)
+    // Ties go to the smallest class, so that the prediction does not depend on the order of the HashMap
+    pub(crate) fn majority_class(&self) -> Option<ExampleClassType> {
+        self.count_by_class()
+            .into_iter()
+            .max_by(|(c1, n1), (c2, n2)| n1.cmp(n2).then_with(|| c2.cmp(c1)))
+            .map(|(c, _)| c)
+    }
+}
+
+impl TildeDataset for Dataset {
+    fn num_examples(&self) -> usize {
+        self.examples.len()
     }
 
-    pub(crate) fn majority_class(&self) -> Option<ExampleClassType> {
-        self.count_by_class().iter().max_by_key(|(_, v)| *v).map(|(c, _)| c).cloned()
+    fn split_on(&self, included_instances: HashSet<Instance>) -> (Dataset, Dataset) {
+        let (left, right) = partition_examples(&self.examples, &included_instances, |e| &e.instance);
+        (Dataset { examples: left }, Dataset { examples: right })
+    }
+
+    fn impurity(&self) -> f64 {
+        entropy(self)
+    }
+
+    fn summary(&self) -> String {
+        self.count_by_class().iter().sorted().map(|(class, count)| format!("{}: {}", class, count)).join(", ")
     }
 }
 
@@ -76,12 +90,6 @@ fn _entropy(counts: &[usize]) -> f64 {
         .sum()
 }
 
-pub(super) fn weighted_information_gain(before: &Dataset, after: [&Dataset; 2]) -> f64 {
-    let weighted_entropy_after =
-        after.iter().map(|d| d.examples.len() as f64 * entropy(d)).sum::<f64>() / before.examples.len() as f64;
-    entropy(before) - weighted_entropy_after
-}
-
 impl ClassificationTask {
     const INSTANCE_VAR_NAME: &'static str = "instance_0";
     const CLASS_VAR_NAME: &'static str = "class_0";
@@ -112,7 +120,7 @@ impl ClassificationTask {
                 let row = row_result?;
                 Ok::<_, typedb_driver::Error>(Example {
                     instance: row.get(ClauseVariable::INSTANCE_VAR_NAME).unwrap().unwrap().into(),
-                    class: row.get(Self::CLASS_VAR_NAME).unwrap().unwrap().try_get_boolean().unwrap(),
+                    class: class_label(row.get(Self::CLASS_VAR_NAME).unwrap().unwrap().try_get_value().unwrap()),
                 })
             })
             .collect::<Result<Vec<Example>, _>>()?;
@@ -121,11 +129,34 @@ impl ClassificationTask {
         Ok(Self { class_type, target_type, dataset })
     }
 
-    pub(super) fn initial_clause(&self, language: &HypothesisLanguage) -> Clause {
-        Clause::new_empty().extend_with_isa(
-            &ClauseVariable(ClauseVariable::INSTANCE_VAR_NAME.to_owned()),
-            &self.target_type,
-            &language.schema,
-        )
+    // Examples by the label of their class (see crate::class_label), e.g. from crate::memory::FactStore.
+    pub fn from_examples(
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        class_attribute_label: &str,
+        examples_by_class: &HashMap<String, HashSet<Instance>>,
+    ) -> Self {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let class_type = language.lookup_type(class_attribute_label).expect("class_type not found");
+        let examples = examples_by_class
+            .iter()
+            .sorted_by_key(|(class, _)| class.as_str())
+            .flat_map(|(class, instances)| {
+                instances.iter().map(|instance| Example { instance: instance.clone(), class: class.clone() })
+            })
+            .collect();
+        Self { class_type, target_type, dataset: Dataset { examples } }
+    }
+}
+
+impl TildeTask for ClassificationTask {
+    type Dataset = Dataset;
+
+    fn initial_clause(&self, language: &HypothesisLanguage) -> Clause {
+        Clause::new_from_head(std::slice::from_ref(&self.target_type), &language.schema)
+    }
+
+    fn dataset(&self) -> &Dataset {
+        &self.dataset
     }
 }
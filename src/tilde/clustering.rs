@@ -0,0 +1,304 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+use typedb_driver::{Promise, concept::Value};
+
+use crate::{
+    Instance, TypeDBHelper,
+    clause::{Clause, ClauseVariable},
+    language::{HypothesisLanguage, SchemaType},
+    tilde::{
+        tilde::TildeTask,
+        tree::{TildeDataset, partition_examples},
+    },
+};
+
+pub type InstanceDistance = Arc<dyn Fn(&Instance, &Instance) -> f64 + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ClusteringExample {
+    pub instance: Instance,
+    pub values: Vec<f64>, // Empty when clustering with an InstanceDistance
+}
+
+// Either the target attribute values, or a distance over instances, determine how similar examples are.
+#[derive(Clone)]
+pub enum ClusteringDistance {
+    // Squared euclidean distance, each dimension weighted by the inverse of its variance over all examples
+    WeightedEuclidean { weights: Arc<Vec<f64>> },
+    Instance(InstanceDistance),
+}
+
+impl ClusteringDistance {
+    // Each candidate split needs the medoids of both sides, which compare every pair of their examples. So the
+    // distance between each pair of examples is computed once, up front.
+    fn cached(examples: &[ClusteringExample], distance: InstanceDistance) -> Self {
+        let index: HashMap<Instance, usize> =
+            examples.iter().enumerate().map(|(i, example)| (example.instance.clone(), i)).collect();
+        let mut distances = vec![vec![0f64; examples.len()]; examples.len()];
+        for (i, j) in (0..examples.len()).tuple_combinations() {
+            distances[i][j] = distance(&examples[i].instance, &examples[j].instance);
+            distances[j][i] = distances[i][j];
+        }
+        ClusteringDistance::Instance(Arc::new(move |a, b| distances[index[a]][index[b]]))
+    }
+
+    // Each dimension weighted by the inverse of its variance, so that attributes count equally
+    fn weighted(examples: &[ClusteringExample], dimensions: usize) -> Self {
+        let weights = (0..dimensions)
+            .map(|d| {
+                let mean = examples.iter().map(|e| e.values[d]).sum::<f64>() / examples.len() as f64;
+                let variance =
+                    examples.iter().map(|e| (e.values[d] - mean).powi(2)).sum::<f64>() / examples.len() as f64;
+                if variance > 0f64 { 1f64 / variance } else { 0f64 }
+            })
+            .collect();
+        ClusteringDistance::WeightedEuclidean { weights: Arc::new(weights) }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Prototype {
+    Centroid(Vec<f64>),
+    Medoid(Instance),
+}
+
+#[derive(Clone)]
+pub struct ClusteringDataset {
+    pub examples: Vec<ClusteringExample>,
+    pub distance: ClusteringDistance,
+}
+
+impl ClusteringDataset {
+    pub fn members(&self) -> impl Iterator<Item = &Instance> {
+        self.examples.iter().map(|e| &e.instance)
+    }
+
+    pub fn prototype(&self) -> Option<Prototype> {
+        if self.examples.is_empty() {
+            return None;
+        }
+        match &self.distance {
+            ClusteringDistance::WeightedEuclidean { weights } => {
+                let centroid = (0..weights.len())
+                    .map(|d| self.examples.iter().map(|e| e.values[d]).sum::<f64>() / self.examples.len() as f64)
+                    .collect();
+                Some(Prototype::Centroid(centroid))
+            }
+            ClusteringDistance::Instance(_) => self
+                .examples
+                .iter()
+                .min_by(|a, b| self.total_distance(a).total_cmp(&self.total_distance(b)))
+                .map(|medoid| Prototype::Medoid(medoid.instance.clone())),
+        }
+    }
+
+    fn distance_to(&self, example: &ClusteringExample, prototype: &Prototype) -> f64 {
+        match (&self.distance, prototype) {
+            (ClusteringDistance::WeightedEuclidean { weights }, Prototype::Centroid(centroid)) => {
+                (0..weights.len()).map(|d| weights[d] * (example.values[d] - centroid[d]).powi(2)).sum()
+            }
+            (ClusteringDistance::Instance(distance), Prototype::Medoid(medoid)) => distance(&example.instance, medoid),
+            _ => unreachable!("Prototype is computed from the same distance"),
+        }
+    }
+
+    fn total_distance(&self, candidate: &ClusteringExample) -> f64 {
+        let ClusteringDistance::Instance(distance) = &self.distance else { unreachable!() };
+        self.examples.iter().map(|e| distance(&candidate.instance, &e.instance)).sum()
+    }
+}
+
+impl TildeDataset for ClusteringDataset {
+    fn num_examples(&self) -> usize {
+        self.examples.len()
+    }
+
+    fn split_on(&self, included_instances: HashSet<Instance>) -> (Self, Self) {
+        let (left, right) = partition_examples(&self.examples, &included_instances, |e| &e.instance);
+        (
+            ClusteringDataset { examples: left, distance: self.distance.clone() },
+            ClusteringDataset { examples: right, distance: self.distance.clone() },
+        )
+    }
+
+    // Mean distance to the prototype. For the weighted euclidean distance, this is the sum of weighted variances.
+    fn impurity(&self) -> f64 {
+        let Some(prototype) = self.prototype() else { return 0f64 };
+        self.examples.iter().map(|e| self.distance_to(e, &prototype)).sum::<f64>() / self.examples.len() as f64
+    }
+
+    fn summary(&self) -> String {
+        match self.prototype() {
+            Some(Prototype::Centroid(centroid)) => {
+                format!("centroid: [{}]", centroid.iter().map(|v| format!("{:.4}", v)).join(", "))
+            }
+            Some(Prototype::Medoid(medoid)) => format!("medoid: {:?}", medoid),
+            None => "empty".to_owned(),
+        }
+    }
+}
+
+pub struct ClusteringTask {
+    pub target_type: SchemaType,          // The type we're clustering.
+    pub attribute_types: Vec<SchemaType>, // The attributes compared, unless clustering with an InstanceDistance.
+    pub dataset: ClusteringDataset,
+}
+
+impl ClusteringTask {
+    const VALUE_VAR_NAME: &'static str = "value_0";
+
+    // Clusters instances of the target type by the integer/double attributes they own
+    pub fn discover(
+        typedb: &TypeDBHelper,
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        attribute_labels: &[&str],
+    ) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let attribute_types: Vec<SchemaType> = attribute_labels
+            .iter()
+            .map(|label| language.lookup_type(label).expect("attribute_type not found"))
+            .collect();
+        let (instance, value) = (ClauseVariable::INSTANCE_VAR_NAME, Self::VALUE_VAR_NAME);
+        let tx = typedb.read_tx()?;
+        // Rows of each instance, with its value if the query has one
+        let read_instances = |query: String| {
+            tx.query(query)
+                .resolve()?
+                .into_rows()
+                .map(|row| {
+                    let row = row?;
+                    Ok((
+                        Instance::from(row.get(instance).unwrap().unwrap()),
+                        row.get(value).ok().flatten().map(|value| value.try_get_value().unwrap().clone()),
+                    ))
+                })
+                .collect::<Result<Vec<(Instance, Option<Value>)>, typedb_driver::Error>>()
+        };
+        let instances: Vec<Instance> = read_instances(format!("match ${instance} isa {target_type};"))?
+            .into_iter()
+            .map(|(instance, _)| instance)
+            .collect();
+
+        // Each attribute is read on its own, so that instances missing some attributes are still clustered.
+        // Several values of an attribute are averaged. Missing values are the mean over the instances that have one.
+        let mut columns: Vec<HashMap<Instance, f64>> = Vec::new();
+        for label in attribute_labels {
+            let mut values: HashMap<Instance, Vec<f64>> = HashMap::new();
+            let rows = read_instances(format!("match ${instance} isa {target_type}, has {label} ${value};"))?;
+            for (instance, value) in rows {
+                let value = match value.unwrap() {
+                    Value::Integer(integer) => integer as f64,
+                    Value::Double(double) => double,
+                    other => {
+                        return Err(typedb_driver::Error::Other(format!(
+                            "Expected clustering attribute {label} to be integer or double, got: {other:?}"
+                        )));
+                    }
+                };
+                values.entry(instance).or_default().push(value);
+            }
+            columns.push(
+                values
+                    .into_iter()
+                    .map(|(instance, values)| (instance, values.iter().sum::<f64>() / values.len() as f64))
+                    .collect(),
+            );
+        }
+        let means: Vec<f64> = columns
+            .iter()
+            .map(|column| if column.is_empty() { 0f64 } else { column.values().sum::<f64>() / column.len() as f64 })
+            .collect();
+        let examples: Vec<ClusteringExample> = instances
+            .into_iter()
+            .map(|instance| {
+                let values =
+                    columns.iter().zip(&means).map(|(column, mean)| *column.get(&instance).unwrap_or(mean)).collect();
+                ClusteringExample { instance, values }
+            })
+            .collect();
+
+        let distance = ClusteringDistance::weighted(&examples, attribute_types.len());
+        Ok(Self { target_type, attribute_types, dataset: ClusteringDataset { examples, distance } })
+    }
+
+    // Clusters instances of the target type by a user-supplied distance
+    pub fn discover_with_distance(
+        typedb: &TypeDBHelper,
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        distance: InstanceDistance,
+    ) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let query = format!("match ${} isa {};", ClauseVariable::INSTANCE_VAR_NAME, target_type);
+        let tx = typedb.read_tx()?;
+        let examples = tx
+            .query(query.as_str())
+            .resolve()?
+            .into_rows()
+            .map(|row_result| {
+                let row = row_result?;
+                Ok::<_, typedb_driver::Error>(ClusteringExample {
+                    instance: row.get(ClauseVariable::INSTANCE_VAR_NAME).unwrap().unwrap().into(),
+                    values: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<ClusteringExample>, _>>()?;
+        let distance = ClusteringDistance::cached(&examples, distance);
+        Ok(Self { target_type, attribute_types: Vec::new(), dataset: ClusteringDataset { examples, distance } })
+    }
+
+    // Instances with the values of the attributes, in order, read elsewhere, e.g. from crate::memory::FactStore.
+    pub fn from_examples(
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        attribute_labels: &[&str],
+        values: &HashMap<Instance, Vec<f64>>,
+    ) -> Self {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let attribute_types: Vec<SchemaType> = attribute_labels
+            .iter()
+            .map(|label| language.lookup_type(label).expect("attribute_type not found"))
+            .collect();
+        let examples: Vec<ClusteringExample> = values
+            .iter()
+            .sorted_by_key(|(instance, _)| instance.sort_key())
+            .map(|(instance, values)| ClusteringExample { instance: instance.clone(), values: values.clone() })
+            .collect();
+        let distance = ClusteringDistance::weighted(&examples, attribute_types.len());
+        Self { target_type, attribute_types, dataset: ClusteringDataset { examples, distance } }
+    }
+
+    // Instances read elsewhere, clustered by a user-supplied distance
+    pub fn from_examples_with_distance(
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        instances: &HashSet<Instance>,
+        distance: InstanceDistance,
+    ) -> Self {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let examples: Vec<ClusteringExample> = instances
+            .iter()
+            .sorted_by_key(|instance| instance.sort_key())
+            .map(|instance| ClusteringExample { instance: instance.clone(), values: Vec::new() })
+            .collect();
+        let distance = ClusteringDistance::cached(&examples, distance);
+        Self { target_type, attribute_types: Vec::new(), dataset: ClusteringDataset { examples, distance } }
+    }
+}
+
+impl TildeTask for ClusteringTask {
+    type Dataset = ClusteringDataset;
+
+    fn initial_clause(&self, language: &HypothesisLanguage) -> Clause {
+        Clause::new_from_head(std::slice::from_ref(&self.target_type), &language.schema)
+    }
+
+    fn dataset(&self) -> &ClusteringDataset {
+        &self.dataset
+    }
+}
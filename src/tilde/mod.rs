@@ -1,6 +1,8 @@
 use typedb_driver::Promise;
-mod classification;
+pub mod classification;
+pub mod clustering;
+pub mod regression;
 pub mod tilde;
-mod tree;
+pub mod tree;
 
 pub type TildeResult<T> = std::result::Result<T, typedb_driver::Error>;
@@ -0,0 +1,156 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use typedb_driver::{
+    Promise,
+    concept::{Value, ValueType},
+};
+
+use crate::{
+    Instance, TypeDBHelper,
+    clause::{Clause, ClauseVariable},
+    language::{HypothesisLanguage, SchemaType},
+    tilde::{
+        tilde::TildeTask,
+        tree::{TildeDataset, partition_examples},
+    },
+};
+
+#[derive(Clone)]
+pub struct RegressionExample {
+    pub instance: Instance,
+    pub value: f64,
+}
+
+#[derive(Clone)]
+pub struct RegressionDataset {
+    pub examples: Vec<RegressionExample>,
+}
+
+impl RegressionDataset {
+    // 0 when empty, as the variance is
+    pub fn mean(&self) -> f64 {
+        if self.examples.is_empty() {
+            return 0f64;
+        }
+        self.examples.iter().map(|e| e.value).sum::<f64>() / self.examples.len() as f64
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.examples.is_empty() {
+            return 0f64;
+        }
+        let mean = self.mean();
+        self.examples.iter().map(|e| (e.value - mean).powi(2)).sum::<f64>() / self.examples.len() as f64
+    }
+}
+
+impl TildeDataset for RegressionDataset {
+    fn num_examples(&self) -> usize {
+        self.examples.len()
+    }
+
+    fn split_on(&self, included_instances: HashSet<Instance>) -> (Self, Self) {
+        let (left, right) = partition_examples(&self.examples, &included_instances, |e| &e.instance);
+        (RegressionDataset { examples: left }, RegressionDataset { examples: right })
+    }
+
+    fn impurity(&self) -> f64 {
+        self.variance()
+    }
+
+    fn summary(&self) -> String {
+        format!("mean: {:.4}, variance: {:.4}", self.mean(), self.variance())
+    }
+}
+
+pub struct RegressionTask {
+    pub target_type: SchemaType, // The type we're predicting a value for.
+    pub value_type: SchemaType,  // The integer or double attribute we predict.
+    pub dataset: RegressionDataset,
+}
+
+impl RegressionTask {
+    const VALUE_VAR_NAME: &'static str = "value_0";
+
+    pub fn discover(
+        typedb: &TypeDBHelper,
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        value_attribute_label: &str,
+    ) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let value_type = language.lookup_type(value_attribute_label).expect("value_type not found");
+        if !matches!(value_type.value_type(), Some(ValueType::Integer | ValueType::Double)) {
+            return Err(typedb_driver::Error::Other(format!(
+                "Expected regression target {value_attribute_label} to be integer or double"
+            )));
+        }
+        let query = format!(
+            "match ${} isa {}, has {} ${};",
+            ClauseVariable::INSTANCE_VAR_NAME,
+            target_type,
+            value_attribute_label,
+            Self::VALUE_VAR_NAME
+        );
+        let tx = typedb.read_tx()?;
+        let examples = tx
+            .query(query.as_str())
+            .resolve()?
+            .into_rows()
+            .map(|row_result| {
+                let row = row_result?;
+                let value = match row.get(Self::VALUE_VAR_NAME).unwrap().unwrap().try_get_value().unwrap() {
+                    Value::Integer(integer) => *integer as f64,
+                    Value::Double(double) => *double,
+                    other => {
+                        return Err(typedb_driver::Error::Other(format!(
+                            "Expected regression target to be integer or double, got: {other:?}"
+                        )));
+                    }
+                };
+                Ok::<_, typedb_driver::Error>(RegressionExample {
+                    instance: row.get(ClauseVariable::INSTANCE_VAR_NAME).unwrap().unwrap().into(),
+                    value,
+                })
+            })
+            .collect::<Result<Vec<RegressionExample>, _>>()?;
+        let dataset = RegressionDataset { examples };
+
+        Ok(Self { target_type, value_type, dataset })
+    }
+
+    // Examples with their values read elsewhere, e.g. from crate::memory::FactStore.
+    pub fn from_examples(
+        language: &HypothesisLanguage,
+        target_type_label: &str,
+        value_attribute_label: &str,
+        values: &HashMap<Instance, f64>,
+    ) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let value_type = language.lookup_type(value_attribute_label).expect("value_type not found");
+        if !matches!(value_type.value_type(), Some(ValueType::Integer | ValueType::Double)) {
+            return Err(typedb_driver::Error::Other(format!(
+                "Expected regression target {value_attribute_label} to be integer or double"
+            )));
+        }
+        let examples = values
+            .iter()
+            .sorted_by_key(|(instance, _)| instance.sort_key())
+            .map(|(instance, value)| RegressionExample { instance: instance.clone(), value: *value })
+            .collect();
+        Ok(Self { target_type, value_type, dataset: RegressionDataset { examples } })
+    }
+}
+
+impl TildeTask for RegressionTask {
+    type Dataset = RegressionDataset;
+
+    fn initial_clause(&self, language: &HypothesisLanguage) -> Clause {
+        Clause::new_from_head(std::slice::from_ref(&self.target_type), &language.schema)
+    }
+
+    fn dataset(&self) -> &RegressionDataset {
+        &self.dataset
+    }
+}
@@ -1,21 +1,37 @@
-use super::classification::{ClassificationTask, Dataset};
+use super::{
+    classification::{ClassificationTask, Dataset},
+    clustering::{ClusteringTask, InstanceDistance},
+    regression::RegressionTask,
+};
 use crate::{
-    TypeDBHelper,
+    CoverageOracle, TypeDBHelper,
+    clause::Clause,
     language::HypothesisLanguage,
     tilde::{
         TildeResult,
-        tree::{LeafNode, TildeTree},
+        tree::{LeafNode, TildeConfig, TildeDataset, TildeTree},
     },
 };
 
-pub struct TildeLearningTask {
-    pub typedb: TypeDBHelper,
+// What the tree predicts: A class, or a numeric value.
+pub trait TildeTask {
+    type Dataset: TildeDataset;
+
+    fn initial_clause(&self, language: &HypothesisLanguage) -> Clause;
+
+    fn dataset(&self) -> &Self::Dataset;
+}
 
-    pub task: ClassificationTask, // Label of the type. Used for initial clause.
+// Splits are tested against TypeDB, or any other CoverageOracle such as crate::memory::FactStore.
+pub struct TildeLearningTask<T: TildeTask = ClassificationTask, O: CoverageOracle = TypeDBHelper> {
+    pub oracle: O,
+
+    pub task: T, // Label of the type. Used for initial clause.
     pub language: HypothesisLanguage,
+    pub config: TildeConfig,
 }
 
-impl TildeLearningTask {
+impl TildeLearningTask<ClassificationTask> {
     const INSTANCE_VAR_NAME: &'static str = "instance_0";
     const CLASS_VAR_NAME: &'static str = "class_0";
     const MAX_THEORY_LENGTH: usize = 20;
@@ -26,17 +42,73 @@ impl TildeLearningTask {
         language: HypothesisLanguage,
         target_type_label: &str,
         class_attribute_label: &str,
+        config: TildeConfig,
     ) -> TildeResult<Self> {
         let task = ClassificationTask::discover(&typedb, &language, target_type_label, class_attribute_label)?;
-        Ok(Self { typedb, task, language })
+        Ok(Self { oracle: typedb, task, language, config })
+    }
+}
+
+impl TildeLearningTask<RegressionTask> {
+    // Regression tree predicting an integer or double attribute of the target type
+    pub fn discover_regression(
+        typedb: TypeDBHelper,
+        language: HypothesisLanguage,
+        target_type_label: &str,
+        value_attribute_label: &str,
+        config: TildeConfig,
+    ) -> TildeResult<Self> {
+        let task = RegressionTask::discover(&typedb, &language, target_type_label, value_attribute_label)?;
+        Ok(Self { oracle: typedb, task, language, config })
+    }
+}
+
+impl TildeLearningTask<ClusteringTask> {
+    // Clustering tree over the integer or double attributes of the target type
+    pub fn discover_clustering(
+        typedb: TypeDBHelper,
+        language: HypothesisLanguage,
+        target_type_label: &str,
+        attribute_labels: &[&str],
+        config: TildeConfig,
+    ) -> TildeResult<Self> {
+        let task = ClusteringTask::discover(&typedb, &language, target_type_label, attribute_labels)?;
+        Ok(Self { oracle: typedb, task, language, config })
+    }
+
+    pub fn discover_clustering_with_distance(
+        typedb: TypeDBHelper,
+        language: HypothesisLanguage,
+        target_type_label: &str,
+        distance: InstanceDistance,
+        config: TildeConfig,
+    ) -> TildeResult<Self> {
+        let task = ClusteringTask::discover_with_distance(&typedb, &language, target_type_label, distance)?;
+        Ok(Self { oracle: typedb, task, language, config })
+    }
+}
+
+impl<T: TildeTask, O: CoverageOracle> TildeLearningTask<T, O> {
+    // A task over a dataset read elsewhere, e.g. ClassificationTask::from_examples
+    pub fn new(oracle: O, language: HypothesisLanguage, task: T, config: TildeConfig) -> Self {
+        Self { oracle, task, language, config }
     }
 
-    pub fn deconstruct(self) -> TypeDBHelper {
-        self.typedb
+    pub fn deconstruct(self) -> O {
+        self.oracle
     }
 
-    pub fn search(&self) -> TildeResult<TildeTree> {
-        let mut root = LeafNode::new(self.task.initial_clause(&self.language), self.task.dataset.clone());
-        root.try_split_recursive(&self.typedb, &self.language, 0)
+    pub fn search(&self) -> TildeResult<TildeTree<T::Dataset>> {
+        let config = &self.config;
+        let mut root = LeafNode::new(self.task.initial_clause(&self.language), self.task.dataset().clone());
+        let tree = root.try_split_recursive(&self.oracle, &self.language, config, 0)?;
+        let tree = match config.complexity_penalty {
+            Some(complexity_penalty) => tree.prune(complexity_penalty),
+            None => tree,
+        };
+        Ok(match config.max_leaves {
+            Some(max_leaves) => tree.prune_to_leaves(max_leaves),
+            None => tree,
+        })
     }
 }
@@ -1,60 +1,290 @@
-use std::fmt::{Formatter, Pointer};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{Formatter, Pointer},
+};
 
 use itertools::Itertools;
 use tracing::{Level, event};
 use typedb_driver::answer::concept_document::Leaf;
 
 use crate::{
-    INDENT, Instance, TypeDBHelper,
+    CoverageOracle, INDENT, Instance,
     clause::Clause,
-    language::HypothesisLanguage,
-    tilde::{
-        TildeResult,
-        classification::{Dataset, entropy, weighted_information_gain},
-    },
+    language::{HypothesisLanguage, SchemaType},
+    tilde::{TildeResult, classification::Dataset},
 };
 
-const MIN_SPLIT_EXAMPLES: usize = 4;
-const MIN_SPLIT_ENTROPY: f64 = 1e-6;
-const MIN_SPLIT_GAIN: f64 = 1e-3;
+// Settings of the tree search. The defaults are those TILDE has always used.
+#[derive(Debug, Clone)]
+pub struct TildeConfig {
+    pub min_split_examples: usize,
+    pub min_split_impurity: f64, // Entropy, or variance for regression
+    pub min_split_gain: f64,
+    pub max_lookahead: usize, // Literals added by a single split, if shorter splits do not gain enough
+    pub always_lookahead_one: bool, // TODO: Disable maybe?
+    pub max_depth: Option<usize>, // Inner nodes from the root to any leaf
+    pub complexity_penalty: Option<f64>, // Cost of each leaf when pruning, in impurity per example. See TildeTree::prune
+    pub max_leaves: Option<usize>, // The tree is pruned further, weakest split first, until it has this many leaves
+}
+
+impl Default for TildeConfig {
+    fn default() -> Self {
+        Self {
+            min_split_examples: 4,
+            min_split_impurity: 1e-6,
+            min_split_gain: 1e-3,
+            max_lookahead: 3,
+            always_lookahead_one: true,
+            max_depth: None,
+            complexity_penalty: None,
+            max_leaves: None,
+        }
+    }
+}
+
+impl TildeConfig {
+    pub fn with_min_split_examples(self, min_split_examples: usize) -> Self {
+        Self { min_split_examples, ..self }
+    }
+
+    pub fn with_min_split_impurity(self, min_split_impurity: f64) -> Self {
+        Self { min_split_impurity, ..self }
+    }
+
+    pub fn with_min_split_gain(self, min_split_gain: f64) -> Self {
+        Self { min_split_gain, ..self }
+    }
+
+    pub fn with_max_lookahead(self, max_lookahead: usize) -> Self {
+        Self { max_lookahead, ..self }
+    }
+
+    pub fn with_always_lookahead_one(self, always_lookahead_one: bool) -> Self {
+        Self { always_lookahead_one, ..self }
+    }
+
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth: Some(max_depth), ..self }
+    }
+
+    pub fn with_complexity_penalty(self, complexity_penalty: f64) -> Self {
+        Self { complexity_penalty: Some(complexity_penalty), ..self }
+    }
+
+    pub fn with_max_leaves(self, max_leaves: usize) -> Self {
+        Self { max_leaves: Some(max_leaves), ..self }
+    }
+}
+
+// The examples at a node of the tree. Splits maximise the reduction in impurity.
+pub trait TildeDataset: Clone {
+    fn num_examples(&self) -> usize;
+
+    fn split_on(&self, included_instances: HashSet<Instance>) -> (Self, Self);
+
+    // Entropy of the classes for classification, variance of the target for regression
+    fn impurity(&self) -> f64;
+
+    // Shown when printing the tree, e.g. the count of each class
+    fn summary(&self) -> String;
+}
+
+pub(super) fn weighted_impurity_reduction<D: TildeDataset>(before: &D, after: [&D; 2]) -> f64 {
+    let weighted_impurity_after =
+        after.iter().map(|d| d.num_examples() as f64 * d.impurity()).sum::<f64>() / before.num_examples() as f64;
+    before.impurity() - weighted_impurity_after
+}
 
-const MAX_LOOKAHEAD: usize = 3;
-const ALWAYS_LOOKAHEAD_ONE: bool = true; // TODO: Disable maybe?
+// Examples whose instance is included go left, the rest go right.
+pub(super) fn partition_examples<E: Clone>(
+    examples: &[E],
+    included_instances: &HashSet<Instance>,
+    instance_of: impl Fn(&E) -> &Instance,
+) -> (Vec<E>, Vec<E>) {
+    examples.iter().cloned().partition(|example| included_instances.contains(instance_of(example)))
+}
 
-pub enum TildeTree {
-    Leaf(LeafNode),
-    Inner(InnerNode),
+pub enum TildeTree<D: TildeDataset = Dataset> {
+    Leaf(LeafNode<D>),
+    Inner(InnerNode<D>),
 }
 
-pub struct LeafNode {
+pub struct LeafNode<D: TildeDataset = Dataset> {
     test_prefix: Clause,
-    dataset: Dataset,
+    dataset: D,
 }
 
-pub struct InnerNode {
+pub struct InnerNode<D: TildeDataset = Dataset> {
     test_prefix: Clause,
-    dataset: Dataset, // TODO: Could remove
-    left: Box<TildeTree>,
-    right: Box<TildeTree>,
+    dataset: D, // TODO: Could remove
+    left: Box<TildeTree<D>>,
+    right: Box<TildeTree<D>>,
 }
 
-impl LeafNode {
-    pub(crate) fn new(test_prefix: Clause, dataset: Dataset) -> LeafNode {
+impl<D: TildeDataset> TildeTree<D> {
+    // Left to right. E.g. for the prototype and members of each cluster.
+    pub fn leaves(&self) -> Vec<&LeafNode<D>> {
+        match self {
+            TildeTree::Leaf(leaf) => vec![leaf],
+            TildeTree::Inner(inner) => {
+                let mut leaves = inner.left.leaves();
+                leaves.extend(inner.right.leaves());
+                leaves
+            }
+        }
+    }
+
+    pub fn dataset(&self) -> &D {
+        match self {
+            TildeTree::Leaf(leaf) => &leaf.dataset,
+            TildeTree::Inner(inner) => &inner.dataset,
+        }
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        match self {
+            TildeTree::Leaf(_) => 1,
+            TildeTree::Inner(inner) => inner.left.num_leaves() + inner.right.num_leaves(),
+        }
+    }
+
+    // Minimal cost-complexity pruning (CART): the cost of a tree is the impurity of its leaves, weighted by their
+    // examples as a fraction of all examples, plus complexity_penalty per leaf. Bottom-up, each subtree is collapsed
+    // into a leaf if that does not increase the cost.
+    pub fn prune(self, complexity_penalty: f64) -> Self {
+        let examples = self.dataset().num_examples();
+        if examples == 0 {
+            return self; // Every cost would be 0/0
+        }
+        self.prune_with(complexity_penalty, examples)
+    }
+
+    // Prunes the weakest splits, those which reduce impurity the least per leaf they add, until at most max_leaves.
+    pub fn prune_to_leaves(mut self, max_leaves: usize) -> Self {
+        let examples = self.dataset().num_examples();
+        while examples > 0 && self.num_leaves() > max_leaves.max(1) {
+            let Some(weakest) = self.weakest_link(examples) else {
+                break;
+            };
+            self = self.prune_with(weakest, examples);
+        }
+        self
+    }
+
+    fn prune_with(self, complexity_penalty: f64, examples: usize) -> Self {
+        let TildeTree::Inner(InnerNode { test_prefix, dataset, left, right }) = self else {
+            return self;
+        };
+        let left = Box::new(left.prune_with(complexity_penalty, examples));
+        let right = Box::new(right.prune_with(complexity_penalty, examples));
+        let tree = TildeTree::Inner(InnerNode { test_prefix, dataset, left, right });
+        if tree.complexity(examples).is_some_and(|complexity| complexity <= complexity_penalty) {
+            tree.collapse()
+        } else {
+            tree
+        }
+    }
+
+    // The smallest complexity of any inner node
+    fn weakest_link(&self, examples: usize) -> Option<f64> {
+        let TildeTree::Inner(inner) = self else {
+            return None;
+        };
+        [self.complexity(examples), inner.left.weakest_link(examples), inner.right.weakest_link(examples)]
+            .into_iter()
+            .flatten()
+            .min_by(f64::total_cmp)
+    }
+
+    // The impurity the subtree removes, per leaf it adds. None for a leaf.
+    fn complexity(&self, examples: usize) -> Option<f64> {
+        let TildeTree::Inner(inner) = self else {
+            return None;
+        };
+        let cost_as_leaf = weighted_impurity(&inner.dataset, examples);
+        let cost_of_leaves = self.leaves().iter().map(|leaf| weighted_impurity(&leaf.dataset, examples)).sum::<f64>();
+        Some((cost_as_leaf - cost_of_leaves) / (self.num_leaves() - 1) as f64)
+    }
+
+    pub fn test_prefix(&self) -> &Clause {
+        match self {
+            TildeTree::Leaf(leaf) => &leaf.test_prefix,
+            TildeTree::Inner(inner) => &inner.test_prefix,
+        }
+    }
+
+    // The clause reaching each leaf, left to right: its test prefix holds the tests which succeeded on the way,
+    // and each test which failed is negated as a whole, since an example goes right when no binding satisfies it.
+    pub fn leaf_clauses(&self) -> Vec<(Clause, &LeafNode<D>)> {
+        let mut clauses = Vec::new();
+        self.collect_leaf_clauses(&mut Vec::new(), &mut clauses);
+        clauses
+    }
+
+    fn collect_leaf_clauses<'a>(
+        &'a self,
+        failed: &mut Vec<&'a InnerNode<D>>,
+        clauses: &mut Vec<(Clause, &'a LeafNode<D>)>,
+    ) {
+        match self {
+            TildeTree::Leaf(leaf) => {
+                let clause = failed.iter().enumerate().fold(leaf.test_prefix.clone(), |clause, (i, inner)| {
+                    clause.extend_with_failed_test(inner.left.test_prefix(), &format!("not_{i}"))
+                });
+                clauses.push((clause, leaf));
+            }
+            TildeTree::Inner(inner) => {
+                inner.left.collect_leaf_clauses(failed, clauses);
+                failed.push(inner);
+                inner.right.collect_leaf_clauses(failed, clauses);
+                failed.pop();
+            }
+        }
+    }
+
+    fn collapse(self) -> Self {
+        match self {
+            TildeTree::Inner(InnerNode { test_prefix, dataset, .. }) => {
+                TildeTree::Leaf(LeafNode { test_prefix, dataset })
+            }
+            leaf => leaf,
+        }
+    }
+}
+
+fn weighted_impurity<D: TildeDataset>(dataset: &D, examples: usize) -> f64 {
+    dataset.num_examples() as f64 * dataset.impurity() / examples as f64
+}
+
+impl<D: TildeDataset> LeafNode<D> {
+    pub(crate) fn new(test_prefix: Clause, dataset: D) -> LeafNode<D> {
         Self { test_prefix, dataset }
     }
 
+    pub fn test_prefix(&self) -> &Clause {
+        &self.test_prefix
+    }
+
+    pub fn dataset(&self) -> &D {
+        &self.dataset
+    }
+
     pub(super) fn try_split_recursive(
         self,
-        typedb: &TypeDBHelper,
+        oracle: &impl CoverageOracle,
         language: &HypothesisLanguage,
+        config: &TildeConfig,
         depth: usize,
-    ) -> TildeResult<TildeTree> {
-        let split = self.try_split(typedb, language)?;
+    ) -> TildeResult<TildeTree<D>> {
+        if config.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            return Ok(TildeTree::Leaf(self));
+        }
+        let split = self.try_split(oracle, language, config)?;
         if let TildeTree::Inner(InnerNode { test_prefix, dataset, left, right }) = split {
             let (left, right) = match (*left, *right) {
                 (TildeTree::Leaf(l), TildeTree::Leaf(r)) => (
-                    Box::new(l.try_split_recursive(typedb, language, depth + 1)?),
-                    Box::new(r.try_split_recursive(typedb, language, depth + 1)?),
+                    Box::new(l.try_split_recursive(oracle, language, config, depth + 1)?),
+                    Box::new(r.try_split_recursive(oracle, language, config, depth + 1)?),
                 ),
                 _ => unreachable!(),
             };
@@ -64,20 +294,27 @@ impl LeafNode {
         }
     }
 
-    pub fn try_split(self, typedb: &TypeDBHelper, language: &HypothesisLanguage) -> TildeResult<TildeTree> {
-        // TODO: Consider things like max-depth etc
-        let dont_split = self.dataset.examples.len() < MIN_SPLIT_EXAMPLES || entropy(&self.dataset) < MIN_SPLIT_ENTROPY;
+    pub fn try_split(
+        self,
+        oracle: &impl CoverageOracle,
+        language: &HypothesisLanguage,
+        config: &TildeConfig,
+    ) -> TildeResult<TildeTree<D>> {
+        let dont_split = self.dataset.num_examples() < config.min_split_examples
+            || self.dataset.impurity() < config.min_split_impurity;
         if dont_split {
-            event!(Level::TRACE, "Don't split. Entropy: {}", entropy(&self.dataset));
+            event!(Level::TRACE, "Don't split. Impurity: {}", self.dataset.impurity());
             return Ok(TildeTree::Leaf(self));
         }
         let mut depth = 0;
-        let mut best_split_opt: Option<(f64, Clause, Dataset, Dataset)> = None;
-        while best_split_opt.as_ref().map(|bs| bs.0 < MIN_SPLIT_GAIN).unwrap_or(true) && depth < MAX_LOOKAHEAD {
+        let mut best_split_opt: Option<(f64, Clause, D, D)> = None;
+        while best_split_opt.as_ref().map(|bs| bs.0 < config.min_split_gain).unwrap_or(true)
+            && depth < config.max_lookahead
+        {
             depth += 1;
             let mut refinements = Vec::new();
             refinements.extend(self.test_prefix.refine_to_length(&language, depth));
-            if depth == 1 && ALWAYS_LOOKAHEAD_ONE {
+            if depth == 1 && config.always_lookahead_one {
                 depth += 1;
                 refinements.extend(self.test_prefix.refine_to_length(&language, depth));
             }
@@ -85,9 +322,9 @@ impl LeafNode {
             best_split_opt = refinements
                 .into_iter()
                 .map(|refined| {
-                    let covered_instances = typedb.test_clause(&refined).unwrap(); // TODO: Handle error
+                    let covered_instances = oracle.test_clause(&refined).unwrap(); // TODO: Handle error
                     let (left_ds, right_ds) = self.dataset.split_on(covered_instances);
-                    let gain = weighted_information_gain(&self.dataset, [&left_ds, &right_ds]);
+                    let gain = weighted_impurity_reduction(&self.dataset, [&left_ds, &right_ds]);
                     (gain, refined, left_ds, right_ds)
                 })
                 .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
@@ -110,19 +347,110 @@ impl LeafNode {
             }
         }
     }
+}
 
+impl LeafNode<Dataset> {
     fn target(&self) -> Option<super::classification::ExampleClassType> {
         self.dataset.majority_class()
     }
 }
 
-impl std::fmt::Display for TildeTree {
+// Compiled to TypeQL, each leaf predicts the majority class of its examples. Leaves without examples predict nothing.
+impl TildeTree<Dataset> {
+    const CLASS_VAR_NAME: &'static str = "predicted_class";
+
+    // The clauses of the leaves predicting each class
+    pub fn clauses_by_class(&self) -> BTreeMap<String, Vec<Clause>> {
+        let mut clauses_by_class: BTreeMap<String, Vec<Clause>> = BTreeMap::new();
+        for (clause, leaf) in self.leaf_clauses() {
+            if let Some(class) = leaf.target() {
+                clauses_by_class.entry(class).or_default().push(clause);
+            }
+        }
+        clauses_by_class
+    }
+
+    // A TypeQL function per class, returning the instances the tree assigns to it, e.g. `predicted_person_true`.
+    // Classes whose names are the same once sanitised are told apart by a number, e.g. `predicted_person_a_b_2`.
+    pub fn class_functions(&self, target_type: &SchemaType) -> Vec<String> {
+        let mut names = HashSet::new();
+        self.clauses_by_class()
+            .iter()
+            .map(|(class, clauses)| {
+                let base: String = format!("predicted_{}_{class}", target_type.label())
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+                    .collect();
+                let mut name = base.clone();
+                for n in 2.. {
+                    if names.insert(name.clone()) {
+                        break;
+                    }
+                    name = format!("{base}_{n}");
+                }
+                let branches = branch_clauses(clauses).iter().map(Clause::to_typeql).collect();
+                format!(
+                    "fun {name}() -> {{ {target_type} }}:\nmatch\n{};\nreturn {{ {} }};",
+                    disjunction(branches),
+                    clauses[0].head().iter().join(", ")
+                )
+            })
+            .collect()
+    }
+
+    // The class functions as a `define` query, or None if no leaf predicts a class
+    pub fn class_definition(&self, target_type: &SchemaType) -> Option<String> {
+        let functions = self.class_functions(target_type);
+        if functions.is_empty() {
+            return None;
+        }
+        Some(format!("define\n{}", functions.join("\n")))
+    }
+
+    // A query returning each instance with its predicted class, as a string (see crate::class_label).
+    // None if no leaf predicts a class.
+    pub fn classification_query(&self) -> Option<String> {
+        let (clauses, classes): (Vec<Clause>, Vec<String>) =
+            self.leaf_clauses().into_iter().filter_map(|(clause, leaf)| Some((clause, leaf.target()?))).unzip();
+        if clauses.is_empty() {
+            return None;
+        }
+        let head = clauses[0].head().iter().join(", ");
+        let branches = branch_clauses(&clauses)
+            .iter()
+            .zip(classes)
+            .map(|(clause, class)| {
+                let class = class.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("{};\nlet ${} = \"{class}\"", clause.to_typeql(), Self::CLASS_VAR_NAME)
+            })
+            .collect();
+        Some(format!("match\n{};\nselect {head}, ${};", disjunction(branches), Self::CLASS_VAR_NAME))
+    }
+}
+
+// Leaves bind their own variables, so as branches of a disjunction, each has those outside the head suffixed with its
+// index, as in Clause::new_from_disjunction.
+fn branch_clauses(clauses: &[Clause]) -> Vec<Clause> {
+    match clauses.len() {
+        1 => clauses.to_vec(),
+        _ => clauses.iter().enumerate().map(|(i, clause)| clause.with_suffixed_variables(&format!("or{i}"))).collect(),
+    }
+}
+
+fn disjunction(branches: Vec<String>) -> String {
+    match branches.len() {
+        1 => branches.into_iter().next().unwrap(),
+        _ => branches.iter().map(|branch| format!("{{ {branch}; }}")).join(" or "),
+    }
+}
+
+impl<D: TildeDataset> std::fmt::Display for TildeTree<D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.fmt_with_indent(f, 0)
     }
 }
 
-impl TildeTree {
+impl<D: TildeDataset> TildeTree<D> {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
         let indent = INDENT.repeat(depth);
         match self {
@@ -131,45 +459,33 @@ impl TildeTree {
         }
     }
 }
-impl std::fmt::Display for LeafNode {
+impl<D: TildeDataset> std::fmt::Display for LeafNode<D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.fmt_with_indent(f, 0)
     }
 }
 
-impl LeafNode {
+impl<D: TildeDataset> LeafNode<D> {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
         let indent = INDENT.repeat(depth);
-        let counts: Vec<String> = self
-            .dataset
-            .count_by_class()
-            .iter()
-            .map(|(class, &count)| format!("{}: {}", if *class { "+" } else { "-" }, count))
-            .collect();
-        let total = self.dataset.examples.len();
-        writeln!(f, "{}(samples={}, {}) LEAF [", indent, total, counts.join(", "))?;
+        let total = self.dataset.num_examples();
+        writeln!(f, "{}(samples={}, {}) LEAF [", indent, total, self.dataset.summary())?;
         self.test_prefix.fmt_with_indent(f, depth + 1)?;
         writeln!(f, "{}]", indent)
     }
 }
 
-impl std::fmt::Display for InnerNode {
+impl<D: TildeDataset> std::fmt::Display for InnerNode<D> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.fmt_with_indent(f, 0)
     }
 }
 
-impl InnerNode {
+impl<D: TildeDataset> InnerNode<D> {
     fn fmt_with_indent(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
         let indent = INDENT.repeat(depth);
-        let counts: Vec<String> = self
-            .dataset
-            .count_by_class()
-            .iter()
-            .map(|(class, &count)| format!("{}: {}", if *class { "+" } else { "-" }, count))
-            .collect();
-        let total = self.dataset.examples.len();
-        writeln!(f, "{}(samples={}, {}) INNER [", indent, total, counts.join(", "))?;
+        let total = self.dataset.num_examples();
+        writeln!(f, "{}(samples={}, {}) INNER [", indent, total, self.dataset.summary())?;
         self.test_prefix.fmt_with_indent(f, depth + 1)?;
         writeln!(f, "{}]", indent)?;
 
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use crate::{
+    CoverageOracle, TypeDBHelper,
+    clause::Clause,
+    language::{HypothesisLanguage, SchemaType},
+};
+
+// WARMR: Level-wise search for the clauses which cover at least min_support of the target instances.
+// Since a refinement never covers more than the clause it refines, only frequent clauses are refined.
+// Clauses are tested against TypeDB, or any other CoverageOracle such as crate::memory::FactStore.
+pub struct WarmrTask<O: CoverageOracle = TypeDBHelper> {
+    pub oracle: O,
+
+    pub target_type: SchemaType, // Support is the number of instances of this type covered.
+    pub language: HypothesisLanguage,
+    pub num_instances: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrequentPattern {
+    pub clause: Clause,
+    pub support: usize,
+}
+
+// `body => specialisation \ body`, where the specialisation refines the body.
+#[derive(Debug, Clone)]
+pub struct AssociationRule {
+    pub body: FrequentPattern,
+    pub specialisation: FrequentPattern,
+    pub confidence: f64,
+}
+
+impl WarmrTask {
+    pub fn discover(
+        typedb: TypeDBHelper,
+        language: HypothesisLanguage,
+        target_type_label: &str,
+    ) -> Result<Self, typedb_driver::Error> {
+        Self::new(typedb, language, target_type_label)
+    }
+}
+
+impl<O: CoverageOracle> WarmrTask<O> {
+    pub fn new(oracle: O, language: HypothesisLanguage, target_type_label: &str) -> Result<Self, typedb_driver::Error> {
+        let target_type = language.lookup_type(target_type_label).expect("target_type not found");
+        let initial_clause = Clause::new_from_head(std::slice::from_ref(&target_type), &language.schema);
+        let num_instances = oracle.test_clause(&initial_clause)?.len();
+        Ok(Self { oracle, target_type, language, num_instances })
+    }
+
+    pub fn deconstruct(self) -> O {
+        self.oracle
+    }
+
+    // min_support is a fraction of the target instances. Each level refines the patterns of the previous level once.
+    pub fn search(&self, min_support: f64, max_levels: usize) -> Result<Vec<FrequentPattern>, typedb_driver::Error> {
+        let min_count = (min_support * self.num_instances as f64).ceil() as usize;
+        let initial_clause = Clause::new_from_head(std::slice::from_ref(&self.target_type), &self.language.schema);
+        let initial =
+            FrequentPattern { support: self.oracle.test_clause(&initial_clause)?.len(), clause: initial_clause };
+        if initial.support < min_count {
+            return Ok(Vec::new());
+        }
+
+        let mut frequent = vec![initial.clone()];
+        let mut seen = HashSet::from([initial.clause.canonical_typeql()]);
+        let mut infrequent = HashSet::new();
+        let mut level = vec![initial];
+        for level_index in 1..=max_levels {
+            let mut next_level = Vec::new();
+            for candidate in level.iter().flat_map(|pattern| pattern.clause.refine(&self.language)) {
+                let canonical = candidate.canonical_typeql();
+                if !seen.insert(canonical.clone()) {
+                    continue;
+                }
+                // Infrequent without testing, and so are its refinements at the next level
+                if self.has_infrequent_generalisation(&candidate, &infrequent) {
+                    infrequent.insert(canonical);
+                    continue;
+                }
+                let support = self.oracle.test_clause(&candidate)?.len();
+                if support >= min_count {
+                    next_level.push(FrequentPattern { clause: candidate, support });
+                } else {
+                    infrequent.insert(canonical);
+                }
+            }
+            println!("Level {}: {} frequent patterns", level_index, next_level.len());
+            frequent.extend(next_level.iter().cloned());
+            level = next_level;
+            if level.is_empty() {
+                break;
+            }
+        }
+        Ok(frequent)
+    }
+
+    // Dropping any literal but the first (the head's isa) gives a generalisation, which is at least as frequent.
+    // Variables are compared by where they first appear, since their names depend on where they were introduced.
+    fn has_infrequent_generalisation(&self, candidate: &Clause, infrequent: &HashSet<String>) -> bool {
+        (1..candidate.len())
+            .any(|index| infrequent.contains(&candidate.without(index, &self.language.schema).canonical_typeql()))
+    }
+
+    // Rules between each frequent pattern and its frequent refinements
+    pub fn association_rules(patterns: &[FrequentPattern], min_confidence: f64) -> Vec<AssociationRule> {
+        let mut rules = Vec::new();
+        for body in patterns {
+            for specialisation in patterns {
+                if specialisation.clause.len() <= body.clause.len() || !body.clause.is_prefix_of(&specialisation.clause)
+                {
+                    continue;
+                }
+                let confidence = specialisation.support as f64 / body.support as f64;
+                if confidence >= min_confidence {
+                    rules.push(AssociationRule {
+                        body: body.clone(),
+                        specialisation: specialisation.clone(),
+                        confidence,
+                    });
+                }
+            }
+        }
+        rules.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        rules
+    }
+}
+
+impl std::fmt::Display for AssociationRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "(confidence={:.3}, support={})", self.confidence, self.specialisation.support)?;
+        writeln!(f, "{}", self.body.clause)?;
+        writeln!(f, "=>")?;
+        writeln!(f, "{}", self.specialisation.clause.to_typeql_from(self.body.clause.len()))
+    }
+}
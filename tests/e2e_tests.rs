@@ -2,9 +2,9 @@ use std::{io::Read, path::Path};
 
 use rusty_foil::{
     TypeDBHelper,
-    foil::FoilLearningTask,
+    foil::{FoilConfig, FoilLearningTask},
     language::{HypothesisLanguage, LanguageDiscoveryOption},
-    tilde::tilde::TildeLearningTask,
+    tilde::{tilde::TildeLearningTask, tree::TildeConfig},
 };
 use typedb_driver::{Credentials, DriverOptions, Promise, TypeDBDriver};
 
@@ -66,7 +66,13 @@ fn test_bongard_foil() -> Result<(), Box<dyn std::error::Error>> {
     )?;
     let typedb = TypeDBHelper::new(driver, db_name.to_owned());
     let language = HypothesisLanguage::fetch_from_typedb(&typedb, &[])?;
-    let task = FoilLearningTask::discover(typedb, language, target_type_label.to_owned(), class_label.to_owned())?;
+    let task = FoilLearningTask::discover(
+        typedb,
+        language,
+        target_type_label.to_owned(),
+        class_label.to_owned(),
+        FoilConfig::default(),
+    )?;
 
     let clauses = task.search()?;
     println!("Found {} clauses", clauses.len());
@@ -98,7 +104,7 @@ fn test_bongard_tilde() -> Result<(), Box<dyn std::error::Error>> {
     )?;
     let typedb = TypeDBHelper::new(driver, db_name.to_owned());
     let language = HypothesisLanguage::fetch_from_typedb(&typedb, &options)?;
-    let task = TildeLearningTask::discover(typedb, language, target_type_label, class_label)?;
+    let task = TildeLearningTask::discover(typedb, language, target_type_label, class_label, TildeConfig::default())?;
 
     let tree = task.search()?;
     println!("{}", tree);
@@ -0,0 +1,69 @@
+use rusty_foil::foil::{
+    heuristic::{
+        ClauseHeuristic, Compression, Coverage, FoilGain, LaplaceAccuracy, MEstimate, WeightedRelativeAccuracy,
+    },
+    stopping::{StoppingCriterion, explicit_bits, likelihood_ratio},
+};
+
+fn coverage(positives: f64, negatives: f64) -> Coverage {
+    Coverage { positives, negatives }
+}
+
+// Scores of refinements covering (2, 0), (1, 1) and (0, 2) of a parent covering every example, (2, 6), in 3 literals
+#[test]
+fn test_heuristic_scores() {
+    let total = coverage(2.0, 6.0);
+    let refined = [coverage(2.0, 0.0), coverage(1.0, 1.0), coverage(0.0, 2.0)];
+    let table: [(&dyn ClauseHeuristic, [f64; 3]); 5] = [
+        (&FoilGain, [4.0, 1.0, f64::NEG_INFINITY]),
+        (&LaplaceAccuracy, [0.75, 0.5, 0.25]),
+        (&MEstimate { m: 2.0 }, [0.625, 0.375, 0.125]),
+        (&WeightedRelativeAccuracy, [0.1875, 0.0625, -0.0625]),
+        (&Compression, [-1.0, -3.0, -5.0]),
+    ];
+    for (heuristic, expected) in table {
+        for (refined, expected) in refined.iter().zip(expected) {
+            let score = heuristic.score(total, *refined, total, 3);
+            assert!(
+                score == expected || (score - expected).abs() < 1e-9,
+                "{heuristic:?} scored {refined:?} {score}, expected {expected}"
+            );
+        }
+    }
+}
+
+// log2(8) bits for the number of positives, and log2(8 choose 2) for which two they are
+#[test]
+fn test_explicit_bits() {
+    assert_eq!(explicit_bits(0.0, 8.0), 3.0);
+    assert!((explicit_bits(2.0, 8.0) - (3.0 + 28f64.log2())).abs() < 1e-9);
+    assert!((explicit_bits(8.0, 8.0) - 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_likelihood_ratio() {
+    // Covering positives and negatives in the proportion of the target examples is not significant
+    assert!(likelihood_ratio(coverage(1.0, 3.0), coverage(2.0, 6.0)).abs() < 1e-9);
+    assert!((likelihood_ratio(coverage(15.0, 5.0), coverage(50.0, 50.0)) - 5.2325).abs() < 1e-3);
+    assert!((likelihood_ratio(coverage(14.0, 6.0), coverage(50.0, 50.0)) - 3.2913).abs() < 1e-3);
+}
+
+#[test]
+fn test_allows_clause() {
+    let total = coverage(50.0, 50.0);
+    let significance = StoppingCriterion::Significance { threshold: 3.84 };
+    assert!(significance.allows_clause(coverage(15.0, 5.0), total, 0.0));
+    assert!(!significance.allows_clause(coverage(14.0, 6.0), total, 0.0));
+
+    let precision = StoppingCriterion::MinPrecision(0.75);
+    assert!(precision.allows_clause(coverage(15.0, 5.0), total, 0.0));
+    assert!(!precision.allows_clause(coverage(14.0, 6.0), total, 0.0));
+
+    // The initial clause takes no bits, so it is always allowed. Refinements may not take more than the positives.
+    let encoding = StoppingCriterion::EncodingLength;
+    assert!(encoding.allows_clause(coverage(0.0, 0.0), total, 0.0));
+    assert!(encoding.allows_clause(total, total, 0.0));
+    let bits = explicit_bits(2.0, 100.0);
+    assert!(encoding.allows_clause(coverage(2.0, 0.0), total, bits));
+    assert!(!encoding.allows_clause(coverage(2.0, 0.0), total, bits + 0.1));
+}
@@ -0,0 +1,93 @@
+use itertools::Itertools;
+use rusty_foil::{
+    clause::Clause,
+    language::{LanguageDiscoveryOption, numeric_thresholds},
+    memory::FactStore,
+};
+use typedb_driver::concept::Value;
+
+fn integers(values: &[i64]) -> Vec<Value> {
+    values.iter().map(|value| Value::Integer(*value)).collect()
+}
+
+#[test]
+fn test_numeric_thresholds() {
+    assert_eq!(numeric_thresholds(&[], 3), vec![]);
+    assert_eq!(numeric_thresholds(&integers(&[4, 4, 4]), 3), vec![]);
+
+    // Fewer distinct values than thresholds: every midpoint, rounded down for integers
+    assert_eq!(numeric_thresholds(&integers(&[3, 1, 2, 1]), 3), integers(&[1, 2]));
+    assert_eq!(numeric_thresholds(&[Value::Double(0.5), Value::Double(1.5)], 3), vec![Value::Double(1.0)]);
+
+    // More: one per quantile
+    let thresholds = numeric_thresholds(&integers(&(0..100).collect::<Vec<_>>()), 3);
+    assert_eq!(thresholds, integers(&[24, 49, 74]));
+
+    // Values which are not numbers are ignored
+    let strings = [Value::String("a".to_owned()), Value::String("b".to_owned())];
+    assert_eq!(numeric_thresholds(&strings, 3), vec![]);
+    let mixed = [Value::Integer(1), Value::String("a".to_owned()), Value::Integer(4)];
+    assert_eq!(numeric_thresholds(&mixed, 3), integers(&[2]));
+}
+
+#[test]
+fn test_threshold_refinements_skip_existing() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = "define attribute age, value integer; entity person, owns age;";
+    let data = "insert $a isa person, has age 1; $b isa person, has age 2; $c isa person, has age 3;";
+    let store = FactStore::from_typeql(schema, data)?;
+    let language = store.language(&[LanguageDiscoveryOption::NumericAttributes {
+        type_labels: vec!["age".to_owned()],
+        max_thresholds: 3,
+    }]);
+
+    let initial = Clause::new_from_head(&[language.schema.lookup_type("person").unwrap().clone()], &language.schema);
+    for clause in initial.refine_to_length(&language, 2) {
+        let typeql = clause.to_typeql();
+        let lines: Vec<&str> = typeql.lines().map(|line| line.trim_end_matches(';')).collect();
+        assert_eq!(lines.iter().unique().count(), lines.len(), "Repeated literal in {clause}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_comparisons_of_comparable_attributes() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"define
+        attribute age, value integer; attribute height, value integer; attribute name, value string;
+        entity person, owns age, owns height, owns name;"#;
+    let data = r#"insert
+        $a isa person, has age 1, has height 150, has name "a";
+        $b isa person, has age 2, has height 160, has name "b";"#;
+    let store = FactStore::from_typeql(schema, data)?;
+    let language = store.language(&[
+        LanguageDiscoveryOption::ComparableAttributes { type_labels: vec!["age".to_owned(), "name".to_owned()] },
+        LanguageDiscoveryOption::NumericAttributes { type_labels: vec!["height".to_owned()], max_thresholds: 1 },
+    ]);
+
+    let initial = Clause::new_from_head(&[language.schema.lookup_type("person").unwrap().clone()], &language.schema);
+    for clause in initial.refine_to_length(&language, 3) {
+        let typeql = clause.to_typeql();
+        let comparisons: Vec<(&str, &str, &str)> = typeql
+            .lines()
+            .map(|line| line.trim_end_matches(';'))
+            .filter_map(|line| match line.split(' ').collect::<Vec<_>>()[..] {
+                [lhs, comparator @ ("==" | "!=" | "<"), rhs] if rhs.starts_with('$') => Some((lhs, comparator, rhs)),
+                _ => None,
+            })
+            .collect();
+        for (lhs, _, rhs) in &comparisons {
+            let is_age = |var: &str| var.starts_with("$age");
+            let is_name = |var: &str| var.starts_with("$name");
+            assert!((is_age(lhs) && is_age(rhs)) || (is_name(lhs) && is_name(rhs)), "Incomparable in {clause}");
+        }
+        // `==` and `!=` are symmetric
+        let normalised: Vec<_> = comparisons
+            .iter()
+            .map(|(lhs, comparator, rhs)| match *comparator {
+                "<" => (lhs.min(rhs), *comparator, lhs.max(rhs), lhs < rhs),
+                _ => (lhs.min(rhs), *comparator, lhs.max(rhs), true),
+            })
+            .collect();
+        assert_eq!(normalised.iter().unique().count(), normalised.len(), "Repeated comparison in {clause}");
+    }
+    Ok(())
+}
@@ -0,0 +1,1040 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use itertools::Itertools;
+use rusty_foil::{
+    CoverageOracle, Instance,
+    anomaly::IsolationForestTask,
+    clause::Clause,
+    foil::{FoilConfig, FoilLearningTask},
+    golem::GolemLearningTask,
+    language::{HypothesisLanguage, LanguageDiscoveryOption, Schema, SchemaType},
+    memory::FactStore,
+    progol::ProgolLearningTask,
+    tilde::{
+        classification::ClassificationTask,
+        clustering::{ClusteringDataset, ClusteringTask, InstanceDistance},
+        regression::RegressionTask,
+        tilde::TildeLearningTask,
+        tree::{TildeConfig, TildeTree},
+    },
+    warmr::WarmrTask,
+};
+use typedb_driver::concept::{Value, ValueType};
+
+// People are happy if they are red. Each has a friend, so that refinements can follow the friendship.
+fn setup_store() -> FactStore {
+    let person = SchemaType::entity("person");
+    let friendship = SchemaType::relation("friendship");
+    let friend = SchemaType::role("friendship:friend");
+    let colour = SchemaType::attribute("colour", Some(ValueType::String));
+    let happy = SchemaType::attribute("happy", Some(ValueType::Boolean));
+    let mut schema = Schema::default();
+    for type_ in [&person, &friendship, &colour, &happy] {
+        schema.define_type(type_.clone());
+    }
+    schema.define_owns(&person, &colour);
+    schema.define_owns(&person, &happy);
+    schema.define_relates(&friendship, &friend);
+    schema.define_plays(&person, &friend);
+
+    let mut store = FactStore::new(schema);
+    let colours = ["red", "red", "red", "blue", "green", "blue"];
+    let people: Vec<_> = colours
+        .iter()
+        .map(|colour| {
+            let iid = store.insert("person").unwrap();
+            store.insert_attribute(&iid, "colour", Value::String(colour.to_string())).unwrap();
+            store.insert_attribute(&iid, "happy", Value::Boolean(*colour == "red")).unwrap();
+            iid
+        })
+        .collect();
+    for pair in people.chunks(2) {
+        let friendship = store.insert("friendship").unwrap();
+        store.insert_link(&friendship, "friend", &pair[0]).unwrap();
+        store.insert_link(&friendship, "friend", &pair[1]).unwrap();
+    }
+    store
+}
+
+fn language_options() -> Vec<LanguageDiscoveryOption> {
+    vec![
+        LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["colour".to_owned()] },
+        LanguageDiscoveryOption::Negation { max_length: 1 },
+    ]
+}
+
+// Types and roles which are not in the schema are errors, rather than panics
+#[test]
+fn test_memory_insert_errors() -> Result<(), Box<dyn std::error::Error>> {
+    let mut store = setup_store();
+    assert!(store.insert("company").is_err());
+    assert!(store.insert("colour").is_err());
+    let person = store.insert("person")?;
+    assert!(store.insert_attribute(&person, "person", Value::Boolean(true)).is_err());
+    assert!(store.insert_attribute(&person, "age", Value::Integer(30)).is_err());
+    let friendship = store.insert("friendship")?;
+    assert!(store.insert_link(&friendship, "employee", &person).is_err());
+    assert!(store.insert_link(&person, "friend", &person).is_err());
+    store.insert_link(&friendship, "friendship:friend", &person)?;
+    Ok(())
+}
+
+// A head variable that no literal binds is an error, rather than a panic
+#[test]
+fn test_memory_unbound_head() {
+    let store = setup_store();
+    assert!(store.test_clause(&Clause::new_empty()).is_err());
+}
+
+#[test]
+fn test_memory_refinements() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let people: HashSet<Instance> =
+        store.instances_of("person").into_iter().map(|iid| Instance::new(vec![iid])).collect();
+
+    let initial = Clause::new_from_head(&[language.schema.lookup_type("person").unwrap().clone()], &language.schema);
+    assert_eq!(store.test_clause(&initial)?, people);
+    let refinements = initial.refine_to_length(&language, 2);
+    assert!(!refinements.is_empty());
+    for refined in refinements {
+        let covered = store.test_clause(&refined)?;
+        assert!(covered.is_subset(&people), "{refined} covers non-people");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_memory_refinements_are_distinct() {
+    let store = setup_store();
+    let mut options = language_options();
+    options.push(LanguageDiscoveryOption::UnifyVariables);
+    let language = store.language(&options);
+
+    let initial = Clause::new_from_head(&[language.schema.lookup_type("person").unwrap().clone()], &language.schema);
+    // Two friendships, so that either can be linked to the friends of the other
+    for clause in initial.refine_to_length(&language, 2) {
+        let refinements: Vec<String> = clause.refine(&language).iter().map(Clause::to_typeql).collect();
+        let distinct: HashSet<&String> = refinements.iter().collect();
+        assert_eq!(distinct.len(), refinements.len(), "Duplicate refinements of {clause}");
+    }
+}
+
+#[test]
+fn test_negated_refinements_are_capped() {
+    let store = setup_store();
+    let options = [LanguageDiscoveryOption::Negation { max_length: 2 }];
+    let initial = |language: &HypothesisLanguage| {
+        Clause::new_from_head(&[language.schema.lookup_type("person").unwrap().clone()], &language.schema)
+    };
+    let negations = |language: &HypothesisLanguage| -> Vec<String> {
+        initial(language)
+            .refine(language)
+            .iter()
+            .map(Clause::to_typeql)
+            .filter(|typeql| typeql.contains("not {"))
+            .collect()
+    };
+
+    let language = store.language(&options);
+    let uncapped = negations(&language);
+    assert!(uncapped.len() > 6);
+    // At most 3 of each length, the same ones each time
+    let language = language.with_max_negated_refinements(3);
+    let capped = negations(&language);
+    assert!(!capped.is_empty() && capped.len() <= 6);
+    assert_eq!(negations(&language), capped);
+}
+
+#[test]
+fn test_merged_theory_covers_union() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let initial = Clause::new_from_head(&[language.schema.lookup_type("person").unwrap().clone()], &language.schema);
+    // Clauses through the friendship, whose variables are local to each branch once merged
+    let clauses: Vec<Clause> = initial
+        .refine_to_length(&language, 2)
+        .into_iter()
+        .filter(|clause| clause.to_typeql().contains("links"))
+        .collect();
+    for theory in clauses.chunks(3) {
+        let mut union = HashSet::new();
+        for clause in theory {
+            union.extend(store.test_clause(clause)?);
+        }
+        let merged = Clause::new_from_disjunction(theory).unwrap();
+        assert_eq!(store.test_clause(&merged)?, union, "{merged}");
+
+        // No variable is shared by two branches, unless bound before the disjunction
+        let typeql = merged.to_typeql();
+        let (prefix, disjunction): (Vec<&str>, Vec<&str>) = typeql.lines().partition(|line| !line.contains("} or {"));
+        let bound = variables(&prefix.join(" "));
+        for line in disjunction {
+            let branches: Vec<HashSet<String>> =
+                top_level_branches(line).iter().map(|branch| &variables(branch) - &bound).collect();
+            for (i, j) in (0..branches.len()).flat_map(|i| (i + 1..branches.len()).map(move |j| (i, j))) {
+                assert!(branches[i].is_disjoint(&branches[j]), "Shared branch variables in {merged}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn variables(typeql: &str) -> HashSet<String> {
+    typeql
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .filter(|token| token.starts_with('$'))
+        .map(str::to_owned)
+        .collect()
+}
+
+// The branches of `{ ... } or { ... }`, split at the outermost braces
+fn top_level_branches(line: &str) -> Vec<String> {
+    let mut branches = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in line.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        current.push(c);
+        if depth == 0 && c == '}' {
+            branches.push(std::mem::take(&mut current));
+        }
+    }
+    branches
+}
+
+#[test]
+fn test_memory_foil() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let examples_by_class = store.examples_by_class("person", "happy");
+    let task = FoilLearningTask::from_examples(
+        store,
+        language,
+        "person".to_owned(),
+        "happy".to_owned(),
+        examples_by_class,
+        FoilConfig::default(),
+    )?;
+    assert_eq!(task.positive_examples.len(), 3);
+
+    let theory = task.search()?;
+    let mut covered = HashSet::new();
+    for clause in &theory {
+        covered.extend(task.oracle.test_clause(clause)?);
+    }
+    assert_eq!(covered, task.positive_examples);
+
+    // Clauses become the branches of a disjunction
+    let initial = Clause::new_from_head(&task.head_types, &task.language.schema);
+    let refinements = initial.refine(&task.language);
+    let definition = task.theory_definition(&refinements[..2]).unwrap();
+    println!("{definition}");
+    assert!(definition.contains(" or "));
+    typeql::parse_query(&definition)?;
+    assert!(task.theory_definition(&[]).is_none());
+    Ok(())
+}
+
+// Shape x has the highest gain, but a negative looks like three of its positives, so no refinement completes it.
+// Hill-climbing commits to it; a beam of 2 also keeps colour q, which is complete on its own.
+#[test]
+fn test_memory_foil_beam() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute shape, value string;
+        attribute colour, value string;
+        attribute positive, value boolean;
+        entity item, owns shape, owns colour, owns positive;
+    "#;
+    let examples = [("x", "p", true); 4]
+        .into_iter()
+        .chain([("y", "q", true); 2])
+        .chain([("x", "p", false)])
+        .chain([("y", "p", false); 3])
+        .map(|(shape, colour, positive)| {
+            format!("$_ isa item, has shape \"{shape}\", has colour \"{colour}\", has positive {positive};")
+        })
+        .join("\n");
+    let options =
+        [LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["shape".to_owned(), "colour".to_owned()] }];
+    let learn = |beam_width: usize| -> Result<_, Box<dyn std::error::Error>> {
+        let store = FactStore::from_typeql(schema, &format!("insert\n{examples}"))?;
+        let language = store.language(&options);
+        let examples_by_class = store.examples_by_class("item", "positive");
+        let config = FoilConfig::default().with_max_theory_length(1).with_beam_width(beam_width);
+        let task = FoilLearningTask::from_examples(
+            store,
+            language,
+            "item".to_owned(),
+            "positive".to_owned(),
+            examples_by_class,
+            config,
+        )?;
+        let theory = task.search()?;
+        let covered = task.oracle.test_clause(&theory[0])?;
+        println!("{}\n", theory[0]);
+        Ok((
+            covered.intersection(&task.positive_examples).count(),
+            covered.intersection(&task.negative_examples).count(),
+        ))
+    };
+
+    assert_eq!(learn(1)?, (4, 1));
+    assert_eq!(learn(2)?, (2, 0));
+    Ok(())
+}
+
+// Recursive clauses call the theory on another person, never on the head itself, so they cannot loop on the example
+#[test]
+fn test_foil_recursive_refinements() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let examples_by_class = store.examples_by_class("person", "happy");
+    let config = FoilConfig::default().with_recursive(true);
+    let task = FoilLearningTask::from_examples(
+        store,
+        language,
+        "person".to_owned(),
+        "happy".to_owned(),
+        examples_by_class,
+        config,
+    )?;
+    let initial = Clause::new_from_head(&task.head_types, &task.language.schema);
+    let red = initial.refine(&task.language).into_iter().find(|clause| clause.to_typeql().contains("red")).unwrap();
+    let function = task.theory_function(std::slice::from_ref(&red)).unwrap();
+    println!("{function}");
+    assert_eq!(function, format!("fun predicted_person() -> {{ person }}:\nmatch\n{};\nreturn {{ $instance }};", red));
+
+    assert!(task.recursive_refinements(&initial).is_empty());
+    // A friend of the example, through a friendship
+    let friend = initial
+        .refine_to_length(&task.language, 2)
+        .into_iter()
+        .find(|clause| clause.to_typeql().matches("friend: $").count() == 2)
+        .unwrap();
+    let recursive = task.recursive_refinements(&friend);
+    for clause in &recursive {
+        println!("{clause}\n");
+    }
+    assert_eq!(recursive.len(), 1);
+    assert!(recursive[0].to_typeql().contains("let $friendship__friend_3 in predicted_person()"));
+    // Each call is only added once
+    assert!(task.recursive_refinements(&recursive[0]).is_empty());
+    Ok(())
+}
+
+// Engineers work for tech companies, and cooks for restaurants
+#[test]
+fn test_foil_from_relation() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute job, value string;
+        attribute sector, value string;
+        entity person, owns job, plays employment:employee;
+        entity company, owns sector, plays employment:employer;
+        relation employment, relates employee, relates employer;
+    "#;
+    let data = r#"
+        insert
+        $a isa person, has job "engineer";
+        $b isa person, has job "engineer";
+        $c isa person, has job "cook";
+        $t isa company, has sector "tech";
+        $r isa company, has sector "food";
+        employment (employee: $a, employer: $t);
+        employment (employee: $b, employer: $t);
+        employment (employee: $c, employer: $r);
+    "#;
+    let options =
+        [LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["job".to_owned(), "sector".to_owned()] }];
+    let role_players =
+        vec![("employee".to_owned(), "person".to_owned()), ("employer".to_owned(), "company".to_owned())];
+
+    let store = FactStore::from_typeql(schema, data)?;
+    let language = store.language(&options);
+    let config = FoilConfig::default().with_max_relation_negatives(2);
+    let capped =
+        FoilLearningTask::from_relation(store, language, "employment".to_owned(), role_players.clone(), config)?;
+    assert_eq!(capped.positive_examples.len(), 3);
+    assert_eq!(capped.negative_examples.len(), 2);
+
+    let store = FactStore::from_typeql(schema, data)?;
+    let language = store.language(&options);
+    let task =
+        FoilLearningTask::from_relation(store, language, "employment".to_owned(), role_players, FoilConfig::default())?;
+    assert_eq!(task.negative_examples.len(), 3);
+    // The relation cannot be used to learn itself
+    let initial = Clause::new_from_head(&task.head_types, &task.language.schema);
+    assert!(initial.refine(&task.language).iter().all(|clause| !clause.to_typeql().contains("employment")));
+
+    let theory = task.search()?;
+    let mut covered = HashSet::new();
+    for clause in &theory {
+        println!("{clause}\n");
+        covered.extend(task.oracle.test_clause(clause)?);
+    }
+    assert_eq!(covered, task.positive_examples);
+    Ok(())
+}
+
+// Only boolean classes have a default positive class, and there must be examples to learn from
+#[test]
+fn test_foil_needs_positive_class() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let by_colour = |store: &FactStore| {
+        let mut examples_by_class = store.examples_by_class("person", "happy");
+        let sad = examples_by_class.remove("false").unwrap();
+        let happy = examples_by_class.remove("true").unwrap();
+        examples_by_class.insert("sad".to_owned(), sad);
+        examples_by_class.insert("glad".to_owned(), happy);
+        examples_by_class
+    };
+    let examples_by_class = by_colour(&store);
+    let mut task = FoilLearningTask::from_examples(
+        store,
+        language.clone(),
+        "person".to_owned(),
+        "happy".to_owned(),
+        examples_by_class,
+        FoilConfig::default(),
+    )?;
+    assert!(task.search().is_err());
+    task.set_positive_class("glad");
+    assert_eq!(task.positive_examples.len(), 3);
+    assert!(!task.search()?.is_empty());
+
+    let empty = FoilLearningTask::from_examples(
+        setup_store(),
+        language,
+        "person".to_owned(),
+        "happy".to_owned(),
+        Default::default(),
+        FoilConfig::default(),
+    );
+    assert!(empty.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_memory_tilde() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let examples_by_class = store.examples_by_class("person", "happy");
+    let classification = ClassificationTask::from_examples(&language, "person", "happy", &examples_by_class);
+    let task = TildeLearningTask::new(store, language, classification, TildeConfig::default());
+
+    let tree = task.search()?;
+    println!("{}", tree);
+    assert_eq!(tree.num_leaves(), 2);
+    // The split removes one bit of entropy per example, for one more leaf
+    assert_eq!(tree.prune(1.0).num_leaves(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_memory_tilde_class_definition() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let examples_by_class = store.examples_by_class("person", "happy");
+    // Both classes are `a_b` once sanitised
+    let renamed = examples_by_class
+        .into_iter()
+        .map(|(class, instances)| (if class == "true" { "a b" } else { "a_b" }.to_owned(), instances))
+        .collect();
+    let classification = ClassificationTask::from_examples(&language, "person", "happy", &renamed);
+    let task = TildeLearningTask::new(store, language, classification, TildeConfig::default());
+    let tree = task.search()?;
+    let definition = tree.class_definition(&task.task.target_type).unwrap();
+    println!("{definition}");
+    assert!(definition.contains("fun predicted_person_a_b() -> { person }:"));
+    assert!(definition.contains("fun predicted_person_a_b_2() -> { person }:"));
+    assert!(tree.classification_query().is_some());
+
+    // Without examples, no leaf predicts a class
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let classification = ClassificationTask::from_examples(&language, "person", "happy", &Default::default());
+    let task = TildeLearningTask::new(store, language, classification, TildeConfig::default());
+    let tree = task.search()?;
+    assert_eq!(tree.class_definition(&task.task.target_type), None);
+    assert_eq!(tree.classification_query(), None);
+    Ok(())
+}
+
+// Mentees and mentors are happy, so both subtrees test a mentorship, and the variable has the same name in both
+#[test]
+fn test_memory_tilde_branch_variables() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute happy, value boolean;
+        entity person, owns happy, plays mentorship:mentor, plays mentorship:mentee;
+        relation mentorship, relates mentor, relates mentee;
+    "#;
+    let data = r#"
+        insert
+        $a isa person, has happy true;
+        $b isa person, has happy true;
+        $c isa person, has happy true;
+        $d isa person, has happy true;
+        $e isa person, has happy false;
+        $f isa person, has happy false;
+        mentorship (mentee: $a, mentor: $c);
+        mentorship (mentee: $b, mentor: $d);
+    "#;
+    let store = FactStore::from_typeql(schema, data)?;
+    let language = store.language(&[]);
+    let examples_by_class = store.examples_by_class("person", "happy");
+    let classification = ClassificationTask::from_examples(&language, "person", "happy", &examples_by_class);
+    let task = TildeLearningTask::new(store, language, classification, TildeConfig::default());
+    let tree = task.search()?;
+    println!("{tree}");
+    let happy: Vec<HashSet<String>> = tree
+        .clauses_by_class()
+        .remove("true")
+        .unwrap()
+        .iter()
+        .map(|clause| &variables(&clause.to_typeql()) - &HashSet::from(["$instance".to_owned()]))
+        .collect();
+    assert!(happy.len() == 2 && !happy[0].is_disjoint(&happy[1]));
+
+    let functions = tree.class_functions(&task.task.target_type);
+    let function = functions.into_iter().find(|function| function.contains("predicted_person_true")).unwrap();
+    let query = tree.classification_query().unwrap();
+    for typeql in [function, query] {
+        println!("{typeql}");
+        let (_, disjunction) = typeql.split_once("match\n").unwrap();
+        let branches: Vec<HashSet<String>> =
+            top_level_branches(disjunction).iter().map(|branch| variables(branch)).collect();
+        assert!(branches.len() >= 2);
+        for (first, second) in branches.iter().tuple_combinations() {
+            let shared: HashSet<&String> = first.intersection(second).collect();
+            assert!(
+                shared.iter().all(|var| ["$instance", "$predicted_class"].contains(&var.as_str())),
+                "Shared branch variables in {typeql}"
+            );
+        }
+    }
+    Ok(())
+}
+
+// With as many happy as unhappy people, a leaf predicts the smallest class, whatever the order of the counts
+#[test]
+fn test_memory_tilde_tied_leaf() -> Result<(), Box<dyn std::error::Error>> {
+    for _ in 0..10 {
+        let store = setup_store();
+        let language = store.language(&language_options());
+        let examples_by_class = store.examples_by_class("person", "happy");
+        let classification = ClassificationTask::from_examples(&language, "person", "happy", &examples_by_class);
+        let task = TildeLearningTask::new(store, language, classification, TildeConfig::default().with_max_depth(0));
+        let tree = task.search()?;
+        assert_eq!(tree.num_leaves(), 1);
+        assert_eq!(tree.clauses_by_class().into_keys().collect_vec(), vec!["false".to_owned()]);
+    }
+    Ok(())
+}
+
+// Red things are small and blue things are large, so the tree splits on colour and each leaf predicts its mean size
+#[test]
+fn test_memory_regression_tilde() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute colour, value string;
+        attribute size, value integer;
+        entity thing, owns colour, owns size;
+    "#;
+    let data = r#"
+        insert
+        $a isa thing, has colour "red", has size 10;
+        $b isa thing, has colour "red", has size 12;
+        $c isa thing, has colour "blue", has size 30;
+        $d isa thing, has colour "blue", has size 34;
+    "#;
+    let store = FactStore::from_typeql(schema, data)?;
+    let language =
+        store.language(&[LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["colour".to_owned()] }]);
+    let values = store
+        .examples_by_class("thing", "size")
+        .into_iter()
+        .flat_map(|(size, instances)| instances.into_iter().map(move |instance| (instance, size.parse().unwrap())))
+        .collect();
+    assert!(RegressionTask::from_examples(&language, "thing", "colour", &values).is_err());
+    let regression = RegressionTask::from_examples(&language, "thing", "size", &values)?;
+    assert_eq!(regression.dataset.mean(), 21.5);
+    let task = TildeLearningTask::new(store, language, regression, TildeConfig::default());
+
+    let tree = task.search()?;
+    println!("{tree}");
+    let means: Vec<f64> =
+        tree.leaf_clauses().iter().map(|(_, leaf)| leaf.dataset().mean()).sorted_by(f64::total_cmp).collect();
+    assert_eq!(means, vec![11.0, 32.0]);
+    Ok(())
+}
+
+// Red things are small and blue things are large, so the tree splits on colour into the two clusters
+#[test]
+fn test_memory_clustering_tilde() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute colour, value string;
+        attribute size, value integer;
+        entity thing, owns colour, owns size;
+    "#;
+    let data = r#"
+        insert
+        $a isa thing, has colour "red", has size 1;
+        $b isa thing, has colour "red", has size 2;
+        $c isa thing, has colour "blue", has size 100;
+        $d isa thing, has colour "blue", has size 101;
+    "#;
+    let options = [LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["colour".to_owned()] }];
+    let store = FactStore::from_typeql(schema, data)?;
+    let sizes: HashMap<Instance, f64> = store
+        .examples_by_class("thing", "size")
+        .into_iter()
+        .flat_map(|(size, instances)| instances.into_iter().map(move |instance| (instance, size.parse().unwrap())))
+        .collect();
+    let clusters = |tree: &TildeTree<ClusteringDataset>| -> HashSet<Vec<i64>> {
+        tree.leaf_clauses()
+            .iter()
+            .map(|(_, leaf)| leaf.dataset().members().map(|member| sizes[member] as i64).sorted().collect())
+            .collect()
+    };
+    let expected = HashSet::from([vec![1, 2], vec![100, 101]]);
+
+    let language = store.language(&options);
+    let values = sizes.iter().map(|(instance, size)| (instance.clone(), vec![*size])).collect();
+    let clustering = ClusteringTask::from_examples(&language, "thing", &["size"], &values);
+    let task = TildeLearningTask::new(store, language, clustering, TildeConfig::default());
+    let tree = task.search()?;
+    println!("{tree}");
+    assert_eq!(clusters(&tree), expected);
+
+    // By a distance over instances, which is called once for each pair of them
+    let calls = Arc::new(AtomicUsize::new(0));
+    let distance: InstanceDistance = {
+        let (sizes, calls) = (sizes.clone(), calls.clone());
+        Arc::new(move |a, b| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            (sizes[a] - sizes[b]).abs()
+        })
+    };
+    let store = FactStore::from_typeql(schema, data)?;
+    let language = store.language(&options);
+    let instances = sizes.keys().cloned().collect();
+    let clustering = ClusteringTask::from_examples_with_distance(&language, "thing", &instances, distance);
+    let task = TildeLearningTask::new(store, language, clustering, TildeConfig::default());
+    let tree = task.search()?;
+    println!("{tree}");
+    assert_eq!(clusters(&tree), expected);
+    assert_eq!(calls.load(Ordering::Relaxed), 6);
+    Ok(())
+}
+
+// The only green thing is isolated by a single test, so it is the most anomalous
+#[test]
+fn test_memory_isolation_forest() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute colour, value string;
+        entity thing, owns colour;
+    "#;
+    let colours = ["red", "red", "red", "blue", "blue", "blue", "red", "green"];
+    let data =
+        colours.iter().enumerate().map(|(i, colour)| format!("$t{i} isa thing, has colour \"{colour}\";")).join("\n");
+    let store = FactStore::from_typeql(schema, &format!("insert\n{data}"))?;
+    let green = store.examples_by_class("thing", "colour")["green"].iter().next().unwrap().clone();
+    let language =
+        store.language(&[LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["colour".to_owned()] }]);
+    let task = IsolationForestTask::new(store, language, "thing")?;
+    assert_eq!(task.instances.len(), 8);
+    assert!(task.search(10, 1, 0).is_err());
+
+    let forest = task.search(50, 8, 0)?;
+    let scores = forest.scores(&task.oracle, &task.instances)?;
+    println!("{scores:?}");
+    for (instance, score) in &scores {
+        if *instance != green {
+            assert!(scores[&green] > *score, "{instance:?} scored {score}, the outlier {}", scores[&green]);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_memory_load_typeql() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute name, value string;
+        attribute age, value integer;
+        entity person, owns name, plays employment:employee;
+        entity manager, sub person, owns age;
+        entity company, owns name, plays employment:employer;
+        relation employment, relates employee, relates employer;
+    "#;
+    let data = r#"
+        insert
+        $e isa employment, links (employee: $m, employer: $c);
+        $m isa manager, has name "Alice", has age 42;
+        $p isa person, has name "Bob";
+        $c isa company, has name "Acme";
+        employment (employee: $p, employer: $c);
+    "#;
+    let store = FactStore::from_typeql(schema, data)?;
+    let schema = store.schema();
+    let manager = schema.lookup_type("manager").unwrap();
+    let name = schema.lookup_type("name").unwrap();
+    assert!(schema.owns[manager].contains(name), "Subtypes own what their supertypes own");
+    assert!(schema.plays[manager].contains(&SchemaType::role("employment:employee")));
+    assert_eq!(store.instances_of("person").len(), 2);
+    assert_eq!(store.instances_of("employment").len(), 2);
+
+    let language = store.language(&[]);
+    let employees = Clause::new_from_head(std::slice::from_ref(manager), &language.schema);
+    assert_eq!(store.test_clause(&employees)?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_memory_bongard_tilde() -> Result<(), Box<dyn std::error::Error>> {
+    let store =
+        FactStore::from_typeql_files(Path::new("examples/bongard/schema.tql"), Path::new("examples/bongard/data.tql"))?;
+    let language =
+        store.language(&[LanguageDiscoveryOption::CategoricalAttributes { type_labels: vec!["config".to_owned()] }]);
+    let examples_by_class = store.examples_by_class("bongard-problem", "class");
+    assert_eq!(examples_by_class.values().map(HashSet::len).sum::<usize>(), 392);
+    assert_eq!(examples_by_class["true"].len(), 128);
+
+    let classification = ClassificationTask::from_examples(&language, "bongard-problem", "class", &examples_by_class);
+    // Shallow, to keep the test quick. The first split already separates most of the classes.
+    let config = TildeConfig::default().with_max_depth(2);
+    let task = TildeLearningTask::new(store, language, classification, config);
+    let tree = task.search()?;
+    println!("{}", tree);
+    assert!(tree.num_leaves() > 1);
+    Ok(())
+}
+
+// Records the clauses it tests
+struct RecordingOracle {
+    store: FactStore,
+    tested: RefCell<Vec<String>>,
+}
+
+impl CoverageOracle for RecordingOracle {
+    fn test_clause_with_functions(
+        &self,
+        clause: &Clause,
+        functions: &[String],
+    ) -> Result<HashSet<Instance>, typedb_driver::Error> {
+        self.tested.borrow_mut().push(clause.to_typeql());
+        self.store.test_clause_with_functions(clause, functions)
+    }
+}
+
+// Only one person has a nickname, so no refinement adding a nickname to a frequent clause is worth testing
+#[test]
+fn test_warmr_skips_infrequent_generalisations() -> Result<(), Box<dyn std::error::Error>> {
+    let person = SchemaType::entity("person");
+    let colour = SchemaType::attribute("colour", Some(ValueType::String));
+    let nickname = SchemaType::attribute("nickname", Some(ValueType::String));
+    let mut schema = Schema::default();
+    for type_ in [&person, &colour, &nickname] {
+        schema.define_type(type_.clone());
+    }
+    schema.define_owns(&person, &colour);
+    schema.define_owns(&person, &nickname);
+    let mut store = FactStore::new(schema);
+    for (index, colour) in ["red", "red", "blue", "green"].iter().enumerate() {
+        let iid = store.insert("person")?;
+        store.insert_attribute(&iid, "colour", Value::String(colour.to_string()))?;
+        if index == 0 {
+            store.insert_attribute(&iid, "nickname", Value::String("Red".to_owned()))?;
+        }
+    }
+
+    let type_labels = vec!["colour".to_owned(), "nickname".to_owned()];
+    let language = store.language(&[LanguageDiscoveryOption::ComparableAttributes { type_labels }]);
+    let oracle = RecordingOracle { store, tested: RefCell::new(Vec::new()) };
+    let task = WarmrTask::new(oracle, language, "person")?;
+    let patterns = task.search(0.5, 3)?;
+    assert!(patterns.iter().any(|pattern| pattern.clause.to_typeql().contains("has colour")));
+    assert!(patterns.iter().all(|pattern| !pattern.clause.to_typeql().contains("has nickname")));
+
+    let tested = task.deconstruct().tested.into_inner();
+    let with_nickname: Vec<_> = tested.iter().filter(|clause| clause.contains("$instance has nickname")).collect();
+    assert_eq!(with_nickname.len(), 1, "{with_nickname:#?}");
+    Ok(())
+}
+
+// Each pattern is found once, whatever the order of its literals, and never repeats a literal
+#[test]
+fn test_warmr_patterns_by_level() -> Result<(), Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute colour, value string;
+        attribute size, value string;
+        entity person, owns colour, owns size;
+    "#;
+    let data = r#"
+        insert
+        $a isa person, has colour "red", has size "big";
+        $b isa person, has colour "red", has size "small";
+        $c isa person, has colour "blue", has size "big";
+        $d isa person, has colour "blue", has size "big";
+    "#;
+    let store = FactStore::from_typeql(schema, data)?;
+    let type_labels = vec!["colour".to_owned(), "size".to_owned()];
+    let language = store.language(&[LanguageDiscoveryOption::CategoricalAttributes { type_labels }]);
+    let task = WarmrTask::new(store, language, "person")?;
+    let patterns = task.search(0.25, 3)?;
+    for pattern in &patterns {
+        println!("{}\n", pattern.clause);
+    }
+    // The initial clause, red, blue, big and small, then red and big, red and small, and blue and big.
+    let counts = patterns.iter().counts_by(|pattern| pattern.clause.to_typeql().lines().count());
+    assert_eq!(counts, HashMap::from([(1, 1), (2, 4), (3, 3)]));
+    Ok(())
+}
+
+// Dropping the literal which binds a variable also drops the negation that mentions it, where it would become local
+#[test]
+fn test_without_negation() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let schema = &language.schema;
+    let initial = Clause::new_from_head(&[schema.lookup_type("person").unwrap().clone()], schema);
+    let friendship = initial
+        .refine(&language)
+        .into_iter()
+        .find(|clause| {
+            clause.to_typeql().lines().count() == 2 && clause.to_typeql().contains("links (friend: $instance)")
+        })
+        .unwrap();
+    let relation = friendship.to_typeql().lines().last().unwrap().split_whitespace().next().unwrap().to_owned();
+    let negated = friendship
+        .refine(&language)
+        .into_iter()
+        .find(|clause| clause.to_typeql().contains(&format!("not {{ {relation} links")))
+        .unwrap();
+    println!("{negated}");
+    assert_eq!(negated.without(1, schema).to_typeql(), initial.to_typeql());
+    assert_eq!(negated.without(2, schema).to_typeql(), friendship.to_typeql());
+    Ok(())
+}
+
+// Mentees of a mentor of their own age are happy. Numeric attributes are variables in the bottom clause, with the side
+// of each threshold they fall on, and attributes of equal value share one.
+fn mentors() -> Result<FactStore, Box<dyn std::error::Error>> {
+    let schema = r#"
+        define
+        attribute age, value integer;
+        attribute happy, value boolean;
+        entity person, owns age, owns happy, plays mentorship:mentor, plays mentorship:mentee;
+        relation mentorship, relates mentor, relates mentee;
+    "#;
+    let data = r#"
+        insert
+        $a isa person, has age 40, has happy true;
+        $b isa person, has age 40, has happy false;
+        $c isa person, has age 20, has happy true;
+        $d isa person, has age 20, has happy false;
+        $e isa person, has age 20, has happy false;
+        $f isa person, has age 40, has happy false;
+        mentorship (mentee: $a, mentor: $b);
+        mentorship (mentee: $c, mentor: $d);
+        mentorship (mentee: $e, mentor: $f);
+    "#;
+    Ok(FactStore::from_typeql(schema, data)?)
+}
+
+fn progol_task(store: FactStore) -> Result<ProgolLearningTask<FactStore>, Box<dyn std::error::Error>> {
+    let options =
+        [LanguageDiscoveryOption::NumericAttributes { type_labels: vec!["age".to_owned()], max_thresholds: 1 }];
+    let language = store.language(&options);
+    let examples_by_class = store.examples_by_class("person", "happy");
+    let foil = FoilLearningTask::from_examples(
+        store,
+        language,
+        "person".to_owned(),
+        "happy".to_owned(),
+        examples_by_class,
+        FoilConfig::default(),
+    )?;
+    Ok(ProgolLearningTask::new(foil, 2))
+}
+
+#[test]
+fn test_progol_bottom_clause() -> Result<(), Box<dyn std::error::Error>> {
+    let store = mentors()?;
+    let people: Vec<Instance> = store.instances_of("person").into_iter().map(|iid| Instance::new(vec![iid])).collect();
+    let task = progol_task(store)?;
+    let literals = |clause: &Clause| {
+        clause.to_typeql().lines().map(|line| line.trim_end_matches(';').to_owned()).sorted().collect_vec()
+    };
+    let expected = |extra: &[&str]| {
+        let walked = [
+            "$instance isa person",
+            "$instance has age $age_b1",
+            "$mentorship_b2 isa mentorship",
+            "$mentorship_b2 links (mentee: $instance)",
+            "$person_b3 isa person",
+            "$mentorship_b2 links (mentor: $person_b3)",
+        ];
+        walked.iter().chain(extra).map(|line| line.to_string()).sorted().collect_vec()
+    };
+
+    // The mentor is as old as the mentee, so they share the age variable
+    let bottom = task.bottom_clause(&people[0])?;
+    println!("{bottom}\n");
+    assert_eq!(literals(&bottom), expected(&["$age_b1 > 30", "$person_b3 has age $age_b1"]));
+    let bottom = task.bottom_clause(&people[4])?;
+    println!("{bottom}\n");
+    assert_eq!(literals(&bottom), expected(&["$age_b1 <= 30", "$person_b3 has age $age_b4", "$age_b4 > 30"]));
+    assert!(task.foil.oracle.test_clause(&bottom)?.contains(&people[4]));
+
+    let theory = task.search()?;
+    let mut covered = HashSet::new();
+    for clause in &theory {
+        println!("{clause}\n");
+        covered.extend(task.foil.oracle.test_clause(clause)?);
+    }
+    assert_eq!(covered, task.foil.positive_examples);
+
+    // Within one hop, the walk stops at the mentorship
+    let shallow = ProgolLearningTask::new(task.deconstruct(), 1);
+    let bottom = shallow.bottom_clause(&people[4])?;
+    assert_eq!(bottom.to_typeql().lines().count(), 5);
+    assert!(!bottom.to_typeql().contains("mentor:"));
+    Ok(())
+}
+
+// The LGG of the bottom clauses of two positives keeps the mentor of the same age, and drops their age thresholds.
+#[test]
+fn test_golem() -> Result<(), Box<dyn std::error::Error>> {
+    let store = mentors()?;
+    let people: Vec<Instance> = store.instances_of("person").into_iter().map(|iid| Instance::new(vec![iid])).collect();
+    let progol = progol_task(store)?;
+    let schema = &progol.foil.language.schema;
+    let lgg = progol.bottom_clause(&people[0])?.lgg(&progol.bottom_clause(&people[2])?, schema);
+    println!("{lgg}\n");
+    assert!(!lgg.to_typeql().contains("30"));
+    assert_eq!(variables(&lgg.to_typeql()).len(), 4);
+    assert_eq!(progol.foil.oracle.test_clause(&lgg)?, HashSet::from([people[0].clone(), people[2].clone()]));
+
+    let golem = GolemLearningTask::new(progol, 4, 0, 0);
+    let theory = golem.search()?;
+    assert_eq!(theory.len(), 1);
+    println!("{}\n", theory[0]);
+    assert_eq!(golem.progol.foil.oracle.test_clause(&theory[0])?, golem.progol.foil.positive_examples);
+    Ok(())
+}
+
+// The LGG keeps the literals both clauses share, generalising values to variables and types to common supertypes,
+// so it covers at least what either clause covers.
+#[test]
+fn test_lgg() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let schema = &language.schema;
+    let initial = Clause::new_from_head(&[schema.lookup_type("person").unwrap().clone()], schema);
+    let refinements = initial.refine(&language);
+    let find = |typeql: &str| refinements.iter().find(|clause| clause.to_typeql() == typeql).unwrap();
+    let red = find("$instance isa person;\n$instance has colour \"red\"");
+    let blue = find("$instance isa person;\n$instance has colour \"blue\"");
+
+    assert_eq!(red.lgg(red, schema).to_typeql(), red.to_typeql());
+    let colour = red.lgg(blue, schema);
+    assert_eq!(colour.to_typeql(), "$instance isa person;\n$instance has colour $lgg_1");
+    assert_eq!(store.test_clause(&colour)?.len(), 6);
+    // Literals without a counterpart in the other clause are dropped
+    assert_eq!(red.lgg(&initial, schema).to_typeql(), initial.to_typeql());
+    // Head variables without a type, as in an empty clause, are left untyped
+    assert_eq!(Clause::new_empty().lgg(&Clause::new_empty(), schema).to_typeql(), Clause::new_empty().to_typeql());
+
+    for (first, second) in refinements.iter().tuple_combinations() {
+        let lgg = first.lgg(second, schema);
+        let covered = store.test_clause(&lgg)?;
+        assert!(covered.is_superset(&store.test_clause(first)?), "{lgg} is not more general than {first}");
+        assert!(covered.is_superset(&store.test_clause(second)?), "{lgg} is not more general than {second}");
+    }
+
+    // Types generalise to their nearest common supertype
+    let animal = SchemaType::entity("animal");
+    let cat = SchemaType::entity("cat");
+    let dog = SchemaType::entity("dog");
+    let mut schema = Schema::default();
+    for type_ in [&animal, &cat, &dog] {
+        schema.define_type(type_.clone());
+    }
+    schema.define_sub(&cat, &animal);
+    schema.define_sub(&dog, &animal);
+    let language = HypothesisLanguage::from_schema(schema, &[]);
+    let schema = &language.schema;
+    let initial = Clause::new_from_head(&[animal], schema);
+    let refinements = initial.refine(&language);
+    let find = |typeql: &str| refinements.iter().find(|clause| clause.to_typeql() == typeql).unwrap();
+    let cats = find("$instance isa animal;\n$instance isa cat");
+    let dogs = find("$instance isa animal;\n$instance isa dog");
+    assert_eq!(cats.lgg(cats, schema).to_typeql(), cats.to_typeql());
+    assert_eq!(cats.lgg(dogs, schema).to_typeql(), "$instance isa animal");
+    Ok(())
+}
+
+// Everyone has a friend, so following the friendship adds nothing to being red
+#[test]
+fn test_prune_drops_redundant_literal() -> Result<(), Box<dyn std::error::Error>> {
+    let store = setup_store();
+    let language = store.language(&language_options());
+    let examples_by_class = store.examples_by_class("person", "happy");
+    let task = FoilLearningTask::from_examples(
+        store,
+        language,
+        "person".to_owned(),
+        "happy".to_owned(),
+        examples_by_class,
+        FoilConfig::default(),
+    )?;
+    let (positives, negatives) = (&task.positive_examples, &task.negative_examples);
+    let accuracy = |theory: &[Clause]| -> Result<f64, typedb_driver::Error> {
+        let mut covered = HashSet::new();
+        for clause in theory {
+            covered.extend(task.oracle.test_clause(clause)?);
+        }
+        let correct = positives.intersection(&covered).count() + negatives.difference(&covered).count();
+        Ok(correct as f64 / (positives.len() + negatives.len()) as f64)
+    };
+
+    let initial = Clause::new_from_head(&task.head_types, &task.language.schema);
+    let red =
+        initial.refine(&task.language).into_iter().find(|clause| clause.to_typeql().ends_with("\"red\"")).unwrap();
+    let redundant =
+        red.refine(&task.language).into_iter().find(|clause| clause.to_typeql().contains("friendship")).unwrap();
+    assert_eq!(accuracy(std::slice::from_ref(&redundant))?, 1.0);
+    let pruned = task.prune(vec![redundant.clone()], positives, negatives)?;
+    assert_eq!(pruned.iter().map(Clause::to_typeql).collect::<Vec<_>>(), vec![red.to_typeql()]);
+    // Nothing to validate against, so nothing is pruned
+    let unpruned = task.prune(vec![redundant.clone()], positives, &HashSet::new())?;
+    assert_eq!(unpruned.iter().map(Clause::to_typeql).collect::<Vec<_>>(), vec![redundant.to_typeql()]);
+    assert_eq!(task.search_pruned(0.0, 0)?.len(), task.search()?.len());
+
+    for clause in initial.refine_to_length(&task.language, 2) {
+        let theory = vec![clause];
+        let pruned = task.prune(theory.clone(), positives, negatives)?;
+        assert!(accuracy(&pruned)? >= accuracy(&theory)?, "Pruning {} lowered accuracy", theory[0]);
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::{collections::HashSet, path::Path};
+
+use rusty_foil::{
+    CoverageOracle, Instance,
+    foil::FoilConfig,
+    prolog::{ModeMarker, PrologDataset, Term},
+    tilde::tree::TildeConfig,
+};
+
+fn trains() -> Result<PrologDataset, Box<dyn std::error::Error>> {
+    Ok(PrologDataset::from_files(
+        Path::new("examples/trains/trains.b"),
+        Path::new("examples/trains/trains.f"),
+        Path::new("examples/trains/trains.n"),
+    )?)
+}
+
+#[test]
+fn test_prolog_parse() -> Result<(), Box<dyn std::error::Error>> {
+    let dataset = trains()?;
+    assert_eq!(dataset.positives.len(), 5);
+    assert_eq!(dataset.negatives.len(), 5);
+    assert_eq!(dataset.head_modes[0].predicate, "eastbound");
+    let load = dataset.body_modes.iter().find(|mode| mode.predicate == "load").unwrap();
+    assert_eq!(
+        load.args,
+        vec![
+            (ModeMarker::Input, "car".to_owned()),
+            (ModeMarker::Constant, "shape".to_owned()),
+            (ModeMarker::Constant, "int".to_owned())
+        ]
+    );
+    let fact = dataset.facts.iter().find(|fact| fact.predicate == "load").unwrap();
+    assert_eq!(fact.args, vec![Term::Atom("car_11".to_owned()), Term::Atom("rectangle".to_owned()), Term::Integer(3)]);
+    Ok(())
+}
+
+#[test]
+fn test_prolog_import() -> Result<(), Box<dyn std::error::Error>> {
+    let import = trains()?.to_typeql()?;
+    println!("{}\n{}", import.schema, import.data);
+    assert_eq!(import.target_type_label, "train");
+    assert_eq!(import.class_attribute_label, "eastbound");
+    // The shape relation is declared first, so the attribute is renamed
+    assert_eq!(import.categorical_attribute_labels, vec!["shape-attribute".to_owned()]);
+    assert_eq!(import.numeric_attribute_labels, vec!["int".to_owned()]);
+
+    let store = import.fact_store()?;
+    assert_eq!(store.instances_of("train").len(), 10);
+    assert_eq!(store.instances_of("car").len(), 30);
+    assert_eq!(store.instances_of("short").len(), 21);
+    Ok(())
+}
+
+#[test]
+fn test_prolog_mode_errors() {
+    let mut dataset = PrologDataset::default();
+    dataset.add_background(":- modeb(*, has_car(+train, -car)).").unwrap();
+    let error = dataset.add_background(":- modeb(*, has_car(type(train), -car)).").unwrap_err();
+    println!("{error}");
+    assert!(error.to_string().contains("Expected +type, -type or #type"));
+}
+
+// Untyped arguments take the type of the instance, and two types for one instance are an error
+#[test]
+fn test_prolog_argument_types() -> Result<(), Box<dyn std::error::Error>> {
+    let mut dataset = PrologDataset::default();
+    dataset.add_background(":- modeh(1, eastbound(+train)).\n:- modeb(*, has_car(+train, -car)).")?;
+    dataset.add_background("has_car(t1, c1).\nhas_car(t2, c2).\ncoupled(c1, t2).\nlong(c2).")?;
+    dataset.positives = PrologDataset::parse_facts("eastbound(t1).")?;
+    dataset.negatives = PrologDataset::parse_facts("eastbound(t2).")?;
+    let import = dataset.to_typeql()?;
+    println!("{}\n{}", import.schema, import.data);
+    assert!(import.schema.contains("entity car, plays coupled:arg1, plays has_car:arg2;"));
+    assert!(import.schema.contains("entity long, sub car;"));
+    assert!(!import.schema.contains("entity object"));
+    let store = import.fact_store()?;
+    assert_eq!(store.instances_of("train").len(), 2);
+    assert_eq!(store.instances_of("car").len(), 2);
+
+    dataset.add_background(":- modeb(*, engine(+engine)).\nengine(c1).")?;
+    assert!(dataset.to_typeql().is_err());
+    Ok(())
+}
+
+// Sanitised, `p q` is `p-q`. Then `p-q` would be `p-q-relation`, which is taken too.
+#[test]
+fn test_prolog_label_collisions() -> Result<(), Box<dyn std::error::Error>> {
+    let mut dataset = PrologDataset::default();
+    dataset.add_background("'p-q-relation'(a, b).\n'p q'(a, b).\n'p-q'(a, b).")?;
+    dataset.positives = PrologDataset::parse_facts("t(a).")?;
+    let import = dataset.to_typeql()?;
+    println!("{}", import.schema);
+    let store = import.fact_store()?;
+    for label in ["p-q-relation", "p-q", "p-q-relation-2"] {
+        assert_eq!(store.instances_of(label).len(), 1, "{label}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_prolog_foil() -> Result<(), Box<dyn std::error::Error>> {
+    let import = trains()?.to_typeql()?;
+    // Following has_car alone gains nothing, so look ahead to the properties of the car.
+    let task = import.foil_task(&[], FoilConfig::default().with_lookahead_one(true))?;
+    assert_eq!(task.positive_examples.len(), 5);
+
+    // Eastbound trains have a short, closed car
+    let theory = task.search()?;
+    let mut covered = HashSet::new();
+    for clause in &theory {
+        println!("{clause}");
+        covered.extend(task.oracle.test_clause(clause)?);
+    }
+    assert_eq!(covered, task.positive_examples);
+
+    let definition = task.theory_definition(&theory).unwrap();
+    println!("{definition}");
+    assert!(definition.starts_with("define\nfun predicted_train() -> { train }:"));
+    typeql::parse_query(&definition)?;
+    Ok(())
+}
+
+#[test]
+fn test_prolog_tilde() -> Result<(), Box<dyn std::error::Error>> {
+    let import = trains()?.to_typeql()?;
+    let task = import.tilde_task(&import.language_options(2), TildeConfig::default().with_max_depth(3))?;
+    let tree = task.search()?;
+    println!("{}", tree);
+    assert!(tree.num_leaves() > 1);
+
+    // Compiled, the path to each leaf covers exactly the examples sorted into it
+    let leaf_clauses = tree.leaf_clauses();
+    assert_eq!(leaf_clauses.len(), tree.num_leaves());
+    for (clause, leaf) in leaf_clauses {
+        let examples: HashSet<Instance> =
+            leaf.dataset().examples.iter().map(|example| example.instance.clone()).collect();
+        assert_eq!(task.oracle.test_clause(&clause)?, examples, "{clause}");
+    }
+    let definition = tree.class_definition(&task.task.target_type).unwrap();
+    println!("{definition}");
+    assert!(definition.contains("fun predicted_train_true() -> { train }:"));
+    typeql::parse_query(&definition)?;
+    let query = tree.classification_query().unwrap();
+    println!("{query}");
+    typeql::parse_query(&query)?;
+    Ok(())
+}